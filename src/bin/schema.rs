@@ -1,6 +1,6 @@
 use cosmwasm_schema::write_api;
 
-use iks_oks::msg::{ InitMsg, QueryMsg, HandleMsg};
+use iks_oks::msg::{ InitMsg, QueryMsg, HandleMsg, MigrateMsg};
 
 fn main() {
     // write_api! {
@@ -12,5 +12,6 @@ fn main() {
         instantiate: InitMsg,
         execute: HandleMsg,
         query: QueryMsg,
+        migrate: MigrateMsg,
     }
 }
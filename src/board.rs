@@ -4,82 +4,344 @@ use serde::{Deserialize, Serialize};
 
 use crate::cell::{Cell, Coordinates};
 
+/// Largest width or height a board can have.
+pub const MAX_BOARD_SIZE: u8 = 19;
+/// Most layers a three-dimensional board can have.
+pub const MAX_BOARD_DEPTH: u8 = 8;
+
+/// Steps `(x, y, z)` to the next cell along a row, a column and both diagonals of a layer,
+/// followed by the lines crossing the layers. Each line is covered by exactly one of them.
+const DIRECTIONS: [(i16, i16, i16); 13] = [
+    (0, 1, 0),
+    (1, 0, 0),
+    (1, 1, 0),
+    (1, -1, 0),
+    (0, 0, 1),
+    (1, 0, 1),
+    (-1, 0, 1),
+    (0, 1, 1),
+    (0, -1, 1),
+    (1, 1, 1),
+    (1, -1, 1),
+    (-1, 1, 1),
+    (-1, -1, 1),
+];
+
+fn default_size() -> u8 {
+    3
+}
+
+fn default_depth() -> u8 {
+    1
+}
+
+/// Board dimensions and how many marks in a row win.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BoardConfig {
+    pub width: u8,
+    pub height: u8,
+    pub k: u8,
+    /// Number of layers, 1 for a flat board.
+    #[serde(default = "default_depth")]
+    pub depth: u8,
+}
+impl Default for BoardConfig {
+    fn default() -> Self {
+        Self {
+            width: 3,
+            height: 3,
+            k: 3,
+            depth: 1,
+        }
+    }
+}
+impl BoardConfig {
+    pub fn is_valid(&self) -> bool {
+        (3..=MAX_BOARD_SIZE).contains(&self.width)
+            && (3..=MAX_BOARD_SIZE).contains(&self.height)
+            && (self.depth == 1 || (3..=MAX_BOARD_DEPTH).contains(&self.depth))
+            && self.k >= 3
+            && self.k <= self.width.max(self.height).max(self.depth)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Board {
+    #[serde(default = "default_size")]
+    pub width: u8,
+    #[serde(default = "default_size")]
+    pub height: u8,
+    /// Marks in a row needed to win.
+    #[serde(default = "default_size")]
+    pub k: u8,
+    #[serde(default = "default_depth")]
+    pub depth: u8,
+    /// Layer by layer, each layer row by row.
     pub cells: Vec<Cell>,
 }
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(&BoardConfig::default())
+    }
+}
 impl Board {
-    pub fn new() -> Self {
+    pub fn new(config: &BoardConfig) -> Self {
         let mut cells = Vec::new();
 
-        for i in 0..3 {
-            for j in 0..3 {
-                let coordinates = Coordinates { x: i, y: j };
-                let cell = Cell::new(coordinates);
-                cells.push(cell);
+        for z in 0..config.depth {
+            for i in 0..config.height {
+                for j in 0..config.width {
+                    let coordinates = if config.depth == 1 {
+                        Coordinates::new(i, j)
+                    } else {
+                        Coordinates::new_3d(i, j, z)
+                    };
+                    let cell = Cell::new(coordinates);
+                    cells.push(cell);
+                }
             }
         }
 
-        Self { cells }
+        Self {
+            width: config.width,
+            height: config.height,
+            k: config.k,
+            depth: config.depth,
+            cells,
+        }
+    }
+    /// Index into `cells`, `x` is the row, `y` the column and `z` the layer.
+    /// Flat boards take coordinates without a layer, three-dimensional ones need it.
+    pub fn index(&self, coordinates: &Coordinates) -> Option<usize> {
+        let z = match coordinates.z {
+            Some(z) => z,
+            None if self.depth == 1 => 0,
+            None => return None,
+        };
+        self.cell_index(coordinates.x.into(), coordinates.y.into(), z.into())
+    }
+    fn cell_index(&self, x: i16, y: i16, z: i16) -> Option<usize> {
+        if x < 0
+            || y < 0
+            || z < 0
+            || x >= self.height.into()
+            || y >= self.width.into()
+            || z >= self.depth.into()
+        {
+            return None;
+        }
+        let layer = z as usize * self.height as usize + x as usize;
+        Some(layer * self.width as usize + y as usize)
+    }
+    pub fn is_full(&self) -> bool {
+        self.cells.iter().all(|cell| cell.player.is_some())
     }
     pub fn occupy_cell(&mut self, player: Addr, coordinates: Coordinates, sign: String) -> bool {
-        let cell_index: usize = coordinates.index().into();
-        if self.cells[cell_index].player == None {
+        let cell_index = match self.index(&coordinates) {
+            Some(cell_index) => cell_index,
+            None => return false,
+        };
+        if self.cells[cell_index].player.is_none() {
             self.cells[cell_index].occupy(player, sign);
-            // self.cells[cell_index].player = Some(player);
-            // self.cells[cell_index].sign = sign;
             return true;
         }
 
         false
     }
+    /// Lowest empty cell of `column`, row 0 being the bottom.
+    pub fn drop_target(&self, column: u8) -> Option<Coordinates> {
+        if column >= self.width {
+            return None;
+        }
+        (0..self.height)
+            .map(|x| Coordinates::new(x, column))
+            .find(|coordinates| self.cells[self.index(coordinates).unwrap()].player.is_none())
+    }
+    /// Like `occupy_cell`, for a number instead of a sign.
+    pub fn occupy_cell_with_number(&mut self, player: Addr, coordinates: Coordinates, number: u8) -> bool {
+        if !self.occupy_cell(player, coordinates.clone(), number.to_string()) {
+            return false;
+        }
+        let cell_index = self.index(&coordinates).unwrap();
+        self.cells[cell_index].value = Some(number);
+        true
+    }
+    /// Whether a piece can step from `from` to `to`: to a neighbouring cell in a row or column,
+    /// or diagonally along one of the board's main diagonals.
+    pub fn are_adjacent(&self, from: &Coordinates, to: &Coordinates) -> bool {
+        if self.index(from).is_none() || self.index(to).is_none() || from == to {
+            return false;
+        }
+        let (dx, dy) = (from.x.abs_diff(to.x), from.y.abs_diff(to.y));
+        if dx > 1 || dy > 1 {
+            return false;
+        }
+        let last = self.width - 1;
+        dx == 0
+            || dy == 0
+            || (from.x == from.y && to.x == to.y)
+            || (from.x + from.y == last && to.x + to.y == last)
+    }
+    /// Whether any piece of `player` has an empty neighbouring cell to slide to.
+    pub fn can_slide(&self, player: &Addr) -> bool {
+        let empty = self.cells.iter().filter(|cell| cell.player.is_none());
+        self.cells
+            .iter()
+            .filter(|cell| cell.player.as_ref() == Some(player))
+            .any(|piece| empty.clone().any(|cell| self.are_adjacent(&piece.coordinates, &cell.coordinates)))
+    }
+    /// Empties the cell at `coordinates`, returning whether it was on the board.
+    pub fn clear_cell(&mut self, coordinates: &Coordinates) -> bool {
+        match self.index(coordinates) {
+            Some(cell_index) => {
+                self.cells[cell_index] = self.cells[cell_index].restart();
+                true
+            }
+            None => false,
+        }
+    }
+    /// Signs of all cells in order, `.` for empty ones, to compare positions.
+    pub fn position(&self) -> String {
+        self.cells
+            .iter()
+            .map(|cell| if cell.sign.is_empty() { "." } else { &cell.sign })
+            .collect()
+    }
     pub fn check_for_win(&self, coordinates: Coordinates) -> bool {
-        let win_lines: Vec<Vec<[usize; 2]>> = vec![
-            vec![[1, 2], [4, 8], [3, 6]],
-            vec![[0, 2], [4, 7]],
-            vec![[0, 1], [4, 6], [5, 8]],
-            vec![[4, 5], [0, 6]],
-            vec![[3, 5], [0, 8], [2, 6], [1, 7]],
-            vec![[3, 4], [2, 8]],
-            vec![[7, 8], [2, 4], [0, 3]],
-            vec![[6, 8], [1, 4]],
-            vec![[6, 7], [0, 4], [2, 5]],
-        ];
-        let last_move: usize = coordinates.index().into();
-        let player = self.cells[last_move].player.clone();
-        for i in 0..win_lines[last_move].len() {
-            let line = win_lines[last_move][i];
-            if player == self.cells[line[0]].player && player == self.cells[line[1]].player {
-                return true;
+        self.winning_line(coordinates).is_some()
+    }
+    /// Cells of a line of at least `k` marks through the move at `coordinates`, in board order.
+    pub fn winning_line(&self, coordinates: Coordinates) -> Option<Vec<Coordinates>> {
+        let cell = &self.cells[self.index(&coordinates)?];
+        cell.player.as_ref()?;
+        self.line_through(cell, false, |other| other.player == cell.player)
+    }
+    /// Like `winning_line`, but the line is made of identical signs whoever placed them.
+    pub fn sign_line(&self, coordinates: Coordinates) -> Option<Vec<Coordinates>> {
+        let cell = &self.cells[self.index(&coordinates)?];
+        if cell.sign.is_empty() {
+            return None;
+        }
+        self.line_through(cell, false, |other| other.sign == cell.sign)
+    }
+    /// Like `sign_line`, but only a line of exactly `k` signs counts, longer runs don't.
+    pub fn exact_sign_line(&self, coordinates: Coordinates) -> Option<Vec<Coordinates>> {
+        let cell = &self.cells[self.index(&coordinates)?];
+        if cell.sign.is_empty() {
+            return None;
+        }
+        self.line_through(cell, true, |other| other.sign == cell.sign)
+    }
+    /// A line of `k` numbers through `coordinates` that adds up to `total`.
+    pub fn sum_line(&self, coordinates: &Coordinates, total: u16) -> Option<Vec<Coordinates>> {
+        let coordinates = &self.cells[self.index(coordinates)?].coordinates;
+        self.lines().into_iter().find(|line| {
+            line.contains(coordinates)
+                && line
+                    .iter()
+                    .map(|coordinates| self.cells[self.index(coordinates).unwrap()].value.map(u16::from))
+                    .sum::<Option<u16>>()
+                    == Some(total)
+        })
+    }
+    fn line_through(&self, cell: &Cell, exact: bool, matches: impl Fn(&Cell) -> bool) -> Option<Vec<Coordinates>> {
+        for (dx, dy, dz) in DIRECTIONS {
+            let mut line = self.walk(&cell.coordinates, (-dx, -dy, -dz), &matches);
+            line.reverse();
+            line.push(cell.coordinates.clone());
+            line.extend(self.walk(&cell.coordinates, (dx, dy, dz), &matches));
+            let k = usize::from(self.k);
+            if line.len() == k || (!exact && line.len() > k) {
+                return Some(line);
             }
         }
-        false
+        None
     }
+    // Cells next to `from` in one direction that match the cell at `from`.
+    fn walk(
+        &self,
+        from: &Coordinates,
+        (dx, dy, dz): (i16, i16, i16),
+        matches: &impl Fn(&Cell) -> bool,
+    ) -> Vec<Coordinates> {
+        let mut cells = vec![];
+        let mut x = i16::from(from.x) + dx;
+        let mut y = i16::from(from.y) + dy;
+        let mut z = i16::from(from.z.unwrap_or(0)) + dz;
+        while let Some(cell_index) = self.cell_index(x, y, z) {
+            let cell = &self.cells[cell_index];
+            if !matches(cell) {
+                break;
+            }
+            cells.push(cell.coordinates.clone());
+            x += dx;
+            y += dy;
+            z += dz;
+        }
+        cells
+    }
+    /// Every run of `k` cells in a straight line, e.g. the 8 lines of a classic board
+    /// or the 76 of a 4x4x4 cube.
+    pub fn lines(&self) -> Vec<Vec<Coordinates>> {
+        let mut lines = vec![];
+        for cell in &self.cells {
+            let (x, y, z) = (
+                i16::from(cell.coordinates.x),
+                i16::from(cell.coordinates.y),
+                i16::from(cell.coordinates.z.unwrap_or(0)),
+            );
+            for (dx, dy, dz) in DIRECTIONS {
+                let line = (0..i16::from(self.k))
+                    .map(|i| self.cell_index(x + i * dx, y + i * dy, z + i * dz))
+                    .map(|cell_index| cell_index.map(|cell_index| self.cells[cell_index].coordinates.clone()))
+                    .collect::<Option<Vec<Coordinates>>>();
+                if let Some(line) = line {
+                    lines.push(line);
+                }
+            }
+        }
+        lines
+    }
+    /// Rows of the board, three-dimensional boards are drawn one layer after another.
     pub fn draw_board(&self) -> String {
-        let mut board_look = String::from(
-            "-------------
-        ",
-        );
-        for i in 0..3 {
-            board_look += &"||".to_string();
-            for j in 0..3 {
-                let cell_index: usize = i * 3 + j;
+        if self.depth == 1 {
+            return self.draw_rows(self.cells.chunks(self.width.into()));
+        }
+        let layer_size = self.width as usize * self.height as usize;
+        let mut board_look = String::new();
+        for (z, layer) in self.cells.chunks(layer_size).enumerate() {
+            board_look += &format!("Layer {}\n", z);
+            board_look += &self.draw_rows(layer.chunks(self.width.into()));
+        }
+        board_look
+    }
+    /// Draws the last row first, for boards where row 0 is the bottom.
+    pub fn draw_board_bottom_up(&self) -> String {
+        self.draw_rows(self.cells.chunks(self.width.into()).rev())
+    }
+    fn draw_rows<'a>(&self, rows: impl Iterator<Item = &'a [Cell]>) -> String {
+        let separator = "-".repeat(self.width as usize * 4 + 3);
+        let mut board_look = separator.clone();
+        board_look += "\n";
+        for row in rows {
+            board_look += "||";
+            for cell in row {
                 board_look += " ";
-                board_look += &self.cells[cell_index].sign;
+                board_look += if cell.sign.is_empty() { " " } else { &cell.sign };
                 board_look += " |";
             }
-            board_look += &"|".to_string();
-            board_look += "--------------
-            "
+            board_look += "|\n";
+            board_look += &separator;
+            board_look += "\n";
         }
 
         board_look
     }
     pub fn restart_board(&self) -> Self {
-        let mut cells: Vec<Cell> = vec![];
-        for index in 0..self.cells.len() {
-            cells[index] = self.cells[index].restart();
+        let cells = self.cells.iter().map(Cell::restart).collect();
+        Self {
+            cells,
+            ..self.clone()
         }
-        Self { cells }
     }
 }
@@ -6,20 +6,40 @@ use serde::{Deserialize, Serialize};
 pub struct Coordinates {
     pub x: u8,
     pub y: u8,
+    /// Layer on three-dimensional boards, left out on flat ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<u8>,
 }
 impl Coordinates {
     pub fn new(x: u8, y: u8) -> Self {
-        Self { x, y }
+        Self { x, y, z: None }
     }
-    pub fn index(&self) -> u8 {
-        self.x * 3 + self.y
+    pub fn new_3d(x: u8, y: u8, z: u8) -> Self {
+        Self { x, y, z: Some(z) }
     }
 }
+/// Tentative mark of a quantum move, sitting in two cells until it collapses into one of them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SpookyMark {
+    pub player: Addr,
+    pub sign: String,
+    /// Move that placed the mark.
+    pub subscript: u8,
+    /// The other cell holding the mark.
+    pub partner: Coordinates,
+}
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Cell {
     pub coordinates: Coordinates,
     pub player: Option<Addr>,
     pub sign: String,
+    /// Number placed in numerical rooms, `sign` then shows it.
+    /// In quantum rooms, the move whose mark collapsed into the cell.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<u8>,
+    /// Quantum marks not collapsed yet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spooky: Vec<SpookyMark>,
 }
 impl Cell {
     pub fn new(coordinates: Coordinates) -> Self {
@@ -27,6 +47,8 @@ impl Cell {
             coordinates,
             player: None,
             sign: "".to_string(),
+            value: None,
+            spooky: vec![],
         }
     }
     pub fn restart(&self) -> Self {
@@ -34,6 +56,8 @@ impl Cell {
             coordinates: self.coordinates.clone(),
             player: None,
             sign: "".to_string(),
+            value: None,
+            spooky: vec![],
         }
     }
     pub fn occupy(&mut self, player: Addr, sign: String) -> &Self
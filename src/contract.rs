@@ -1,82 +1,367 @@
-
-use crate::board::Board;
-
-use crate::cell::Coordinates;
+use crate::board::{Board, BoardConfig, MAX_BOARD_DEPTH, MAX_BOARD_SIZE};
 use crate::error::ContractError;
+use crate::legacy::{migrate_game_states, migrate_legacy_layout};
 use crate::msg::{
-    GameStatusResponse, HandleMsg, InitMsg, PlayerTurnResponse, QueryMsg, RoomExistResponse,
-    TableStatusResponse, ListPageResponse,
+    ConfigResponse, GameStatusResponse, HandleMsg, InitMsg, MigrateMsg, PlayerTurnResponse,
+    QueryMsg, QueueStatusResponse, ReceiveMsg, RoomExistResponse, RoomInfo, RoomsResponse, TableStatusResponse,
 };
-use crate::room::{ Room, GameState};
-use crate::state::State;
+use crate::queue::QueueEntry;
+use crate::room::{rooms, GameState, LastMove, Room, RoomStatus};
+use crate::state::{State, MAX_HOUSE_FEE_BPS};
+use crate::variant::{Move, Variant};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
-    SubMsg, Uint128,
+    from_binary, to_binary, Addr, BankMsg, Binary, BlockInfo, Coin, Deps, DepsMut, Env, MessageInfo, Order,
+    Response, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
-use paginate::Pages;
+use cw2::{set_contract_version, CONTRACT};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, Denom, Expiration};
+use cw_storage_plus::Bound;
+use semver::Version;
+
+const CONTRACT_NAME: &str = "crates.io:iks-oks";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// First version that maintains the rooms-by-player indexes.
+const PLAYER_INDEX_VERSION: &str = "0.3.0";
+/// First version that stores `GameState` as a typed enum.
+const TYPED_GAME_STATE_VERSION: &str = "0.4.0";
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+/// Most rooms a listing loads in one query, so a rare `status_filter` can't run out of gas.
+const MAX_SCAN: usize = 100;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InitMsg,
+    info: MessageInfo,
+    msg: InitMsg,
 ) -> Result<Response, ContractError> {
-    State::init_state(deps.storage)?;
+    check_house_fee(msg.house_fee_bps)?;
+    let admin = match msg.admin {
+        Some(admin) => deps.api.addr_validate(&admin)?,
+        None => info.sender,
+    };
+    let accepted_cw20 = validate_addresses(deps.as_ref(), &msg.accepted_cw20)?;
+    let state = State::new(admin, msg.house_fee_bps, msg.accepted_denoms, accepted_cw20);
+    check_accepted_denoms(&state)?;
+    state.init_state(deps.storage)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let new_version = parse_version(CONTRACT_VERSION)?;
+    // Deployments from before versioning never stored a contract version.
+    match CONTRACT.may_load(deps.storage)? {
+        Some(stored) => {
+            if stored.contract != CONTRACT_NAME {
+                return Err(ContractError::MigrationError {
+                    val: format!("Can't migrate from contract {}.", stored.contract),
+                });
+            }
+            let stored_version = parse_version(&stored.version)?;
+            if stored_version > new_version {
+                return Err(ContractError::MigrationError {
+                    val: format!("Can't downgrade from version {}.", stored.version),
+                });
+            }
+            if stored_version < parse_version(TYPED_GAME_STATE_VERSION)? {
+                migrate_game_states(deps.storage)?;
+            }
+            if stored_version < parse_version(PLAYER_INDEX_VERSION)? {
+                Room::rebuild_indexes(deps.storage)?;
+            }
+        }
+        None => {
+            let admin = match msg.admin {
+                Some(admin) => deps.api.addr_validate(&admin)?,
+                None => {
+                    return Err(ContractError::MigrationError {
+                        val: "An admin is required to migrate a legacy deployment.".to_string(),
+                    })
+                }
+            };
+            migrate_legacy_layout(deps.storage, admin)?;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::default())
 }
+
+fn parse_version(version: &str) -> Result<Version, ContractError> {
+    version.parse().map_err(|_| ContractError::MigrationError {
+        val: format!("Invalid version {}.", version),
+    })
+}
+
+fn check_accepted_denoms(state: &State) -> Result<(), ContractError> {
+    if state.accepted_denoms.is_empty() && state.accepted_cw20.is_empty() {
+        return Err(ContractError::StateError {
+            val: "At least one accepted denom is required.".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn check_house_fee(house_fee_bps: u16) -> Result<(), ContractError> {
+    if house_fee_bps > MAX_HOUSE_FEE_BPS {
+        return Err(ContractError::StateError {
+            val: format!("House fee can't be above {} bps.", MAX_HOUSE_FEE_BPS),
+        });
+    }
+    Ok(())
+}
+
+fn validate_addresses(deps: Deps, addresses: &[String]) -> StdResult<Vec<Addr>> {
+    addresses
+        .iter()
+        .map(|address| deps.api.addr_validate(address))
+        .collect()
+}
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: HandleMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        HandleMsg::PlayMove { coordinates, room_number } => play_move(deps, info, coordinates, room_number),
-        HandleMsg::RestartGame { room_number } => restart_game(deps, room_number),
+        HandleMsg::PlayMove { room_number, action } => {
+            play_move(deps, env, info, action, room_number)
+        }
+        HandleMsg::RestartGame { room_number } => restart_game(deps, env, info, room_number),
+        HandleMsg::Resign { room_number } => resign(deps, info, room_number),
         HandleMsg::AddRoom {
             player1,
             player2,
-        } => add_room(deps, player1, player2),
+            denom,
+            stake,
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let room = new_room(player1, Some(player2), denom, stake, options)?;
+            add_room(deps, env, info, room)
+        }
+        HandleMsg::CreateRoom {
+            denom,
+            stake,
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let room = new_room(info.sender.clone(), None, denom, stake, options)?;
+            create_open_room(deps, env, info, room)
+        }
+        HandleMsg::JoinRoom { room_id } => join_room(deps, env, info, room_id),
+        HandleMsg::CancelRoom { room_id } => cancel_room(deps, env, info, room_id),
+        HandleMsg::Challenge {
+            opponent,
+            denom,
+            stake,
+            expires_at,
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let opponent = deps.api.addr_validate(&opponent)?;
+            let room = new_room(info.sender.clone(), Some(opponent), denom, stake, options)?;
+            challenge(deps, env, info, room, expires_at)
+        }
+        HandleMsg::AcceptChallenge { room_id } => accept_challenge(deps, env, info, room_id),
+        HandleMsg::DeclineChallenge { room_id } => decline_challenge(deps, info, room_id),
+        HandleMsg::EnqueueForMatch {
+            denom,
+            stake,
+            variant,
+            move_timeout,
+        } => enqueue_for_match(deps, env, info, denom, stake, variant, move_timeout),
+        HandleMsg::LeaveQueue {} => leave_queue(deps, info),
+        HandleMsg::ClaimTimeout { room_id } => claim_timeout(deps, env, info, room_id),
+        HandleMsg::Deposit { room_number } => deposit(deps, env, info, room_number),
+        HandleMsg::Receive(wrapper) => receive(deps, env, info, wrapper),
+        HandleMsg::Withdraw {
+            denom,
+            amount,
+            recipient,
+        } => withdraw(deps, info, denom, amount, recipient),
+        HandleMsg::UpdateConfig {
+            admin,
+            house_fee_bps,
+            accepted_denoms,
+            accepted_cw20,
+        } => update_config(deps, info, admin, house_fee_bps, accepted_denoms, accepted_cw20),
+    }
+}
+
+/// Tokens that came with a message, either as native coins or through a CW20 `Receive`.
+struct Payment {
+    sender: Addr,
+    denom: Denom,
+    amount: Uint128,
+}
+
+fn native_payment(info: &MessageInfo) -> Result<Option<Payment>, ContractError> {
+    match info.funds.as_slice() {
+        [] => Ok(None),
+        [coin] => Ok(Some(Payment {
+            sender: info.sender.clone(),
+            denom: Denom::Native(coin.denom.clone()),
+            amount: coin.amount,
+        })),
+        _ => Err(ContractError::CustomError {
+            val: "Only one coin can be deposited.".to_string(),
+        }),
+    }
+}
+
+fn denom_name(denom: &Denom) -> String {
+    match denom {
+        Denom::Native(denom) => denom.clone(),
+        Denom::Cw20(address) => address.to_string(),
+    }
+}
+
+/// Checks that the payment is exactly the room's stake.
+fn check_stake(payment: &Payment, room: &Room) -> Result<(), ContractError> {
+    check_payment(payment, &room.denom, room.stake)
+}
+
+fn check_payment(payment: &Payment, denom: &Denom, stake: Uint128) -> Result<(), ContractError> {
+    if stake.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "Room has no stake to deposit.".to_string(),
+        });
+    }
+    if payment.denom != *denom || payment.amount != stake {
+        return Err(ContractError::CustomError {
+            val: format!("Deposit must be exactly {}{}.", stake, denom_name(denom)),
+        });
+    }
+    Ok(())
+}
+
+fn send_tokens(to_address: &Addr, amount: Uint128, denom: &Denom) -> StdResult<SubMsg> {
+    let msg = match denom {
+        Denom::Native(denom) => SubMsg::new(BankMsg::Send {
+            to_address: to_address.to_string(),
+            amount: vec![Coin {
+                amount,
+                denom: denom.clone(),
+            }],
+        }),
+        Denom::Cw20(contract_addr) => SubMsg::new(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: to_address.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+    };
+    Ok(msg)
+}
+
+/// Sends the pot minus the house fee to `winner`, the fee goes to the treasury.
+fn pay_winner(
+    storage: &mut dyn Storage,
+    room: &Room,
+    winner: &Addr,
+) -> Result<Vec<SubMsg>, ContractError> {
+    if room.total_coins_raised.is_zero() {
+        return Ok(vec![]);
+    }
+    let fee = room.house_fee();
+    if !fee.is_zero() {
+        let mut state = State::load_state(storage)?;
+        state.add_to_treasury(&room.denom, fee);
+        state.save_state(storage)?;
+    }
+    let prize = room.total_coins_raised - fee;
+    Ok(vec![send_tokens(winner, prize, &room.denom)?])
+}
+
+/// Splitting the pot hands back both stakes, and evenly splits pots of migrated rooms.
+fn refund_players(room: &Room) -> StdResult<Vec<SubMsg>> {
+    let player2_refund = room.total_coins_raised.multiply_ratio(1u128, 2u128);
+    let player1_refund = room.total_coins_raised - player2_refund;
+    let player2 = room.opponent(&room.player1);
+    let mut messages = vec![];
+    for (player, refund) in [(&room.player1, player1_refund), (&player2, player2_refund)] {
+        if !refund.is_zero() {
+            messages.push(send_tokens(player, refund, &room.denom)?);
+        }
+    }
+    Ok(messages)
+}
+
+/// Hands each stake back to the player who deposited it, pots of migrated rooms are split evenly.
+fn refund_deposits(room: &Room) -> StdResult<Vec<SubMsg>> {
+    if !room.player1_deposited && !room.player2_deposited {
+        return refund_players(room);
+    }
+    let player2 = room.opponent(&room.player1);
+    let mut messages = vec![];
+    for (player, deposited) in [(&room.player1, room.player1_deposited), (&player2, room.player2_deposited)] {
+        if deposited {
+            messages.push(send_tokens(player, room.stake, &room.denom)?);
+        }
     }
+    Ok(messages)
 }
 
 pub fn play_move(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    coordinates: Coordinates,
-    room_number: u8,
+    action: Move,
+    room_number: u64,
 ) -> Result<Response, ContractError> {
-    let mut sent_coins = Uint128::zero();
-    for coin in info.funds.iter() {
-        if coin.denom != "ioc" {
-            return Err(ContractError::CustomError {
-                val: "Only ioc is supported. Invlid token sent.".to_string(),
-            });
-        }
-        sent_coins = sent_coins + coin.amount;
-    }
-
-    if sent_coins.is_zero() {
+    if !info.funds.is_empty() {
         return Err(ContractError::CustomError {
-            val: "No coins sent".to_string(),
+            val: "Moves do not take coins, stakes are deposited up front.".to_string(),
         });
     }
 
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
         return Err(ContractError::RoomError {
             val: "Room does not exist.".to_string(),
         });
     }
     let mut room = room.unwrap();
 
-    let mut state = State::load_state(deps.storage);
-    
-    if room.game_state != GameState::InProgess.to_string()|| room.no_moves == 9 {
+    if room.game_state == GameState::WaitingForOpponent {
+        return Err(ContractError::CustomError {
+            val: "Waiting for both players to deposit their stake.".to_string(),
+        });
+    }
+    if room.game_state != GameState::InProgress || room.board.is_full() {
         return Err(ContractError::CustomError {
             val: "Game ended.".to_string(),
         });
@@ -86,53 +371,25 @@ pub fn play_move(
             val: "It's not your turn or you missed a room.".to_string(),
         });
     }
-    let mut sign = "".to_string();
-
-    if room.turn == room.player1 {
-        sign = "X".to_string();
-    } else {
-        sign = "O".to_string();
-    }
-
-    if !room
-        .board
-        .occupy_cell(room.turn.clone(), coordinates.clone(), sign)
-    {
-        return Err(ContractError::CustomError {
-            val: "Spot is occupied".to_string(),
-        });
+    room.play(&info.sender, action)?;
+    room.last_move = Some(LastMove::from(&env.block));
+    // A collapse is followed by the same player's move, so their clock keeps running.
+    if room.turn != info.sender {
+        room.start_turn(&env.block);
     }
-    room.no_moves = room.no_moves + 1;
 
-    room.total_coins_raised = room.total_coins_raised + sent_coins;
     let mut response = Response::default();
-    if room.board.check_for_win(coordinates.clone()) {
-        room.game_state = GameState::GameWon {
-            player: room.turn.clone(),
-        }.to_string();
-        let raised_coins = room.total_coins_raised.u128() / 2 ;
-        let coins = Uint128::from(raised_coins);
-        state.balance = state.balance + coins;
-        let bankmsg = BankMsg::Send {
-            to_address: room.turn.to_string(),
-            amount: vec![Coin {
-                amount: coins,
-                denom: "ioc".to_string(),
-            }],
-        };
-        state.save_state(deps.storage)?;
-        let submsg = SubMsg::new(bankmsg);
-        response.messages.push(submsg)
-    }
-    
-    if room.no_moves == 9 && room.game_state == GameState::InProgess.to_string() {
-        room.game_state = GameState::Tie.to_string()
+    match &room.game_state {
+        GameState::Won { player, .. } => {
+            response.messages = pay_winner(deps.storage, &room, player)?;
+        }
+        GameState::Tie => {
+            response.messages = refund_players(&room)?;
+        }
+        _ => {}
     }
-
-    if room.turn == room.player1 {
-        room.turn = room.player2.clone();
-    } else {
-        room.turn = room.player1.clone();
+    if room.is_finished() {
+        room.total_coins_raised = Uint128::zero();
     }
 
     Room::save_room(room_number, deps.storage, room.clone())?;
@@ -140,334 +397,3078 @@ pub fn play_move(
     response = response.set_data(to_binary(&room.board).unwrap());
     Ok(response)
 }
-pub fn add_room(
+pub fn add_room(deps: DepsMut, env: Env, info: MessageInfo, room: Room) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    add_seated_room(deps, &env.block, &info.sender, room, payment)
+}
 
+// Only player1 can seat both players, and player2 still has to accept the room
+// like a challenge that doesn't expire. Nobody is put into a game without agreeing to it.
+fn add_seated_room(
     deps: DepsMut,
+    block: &BlockInfo,
+    sender: &Addr,
+    room: Room,
+    payment: Option<Payment>,
+) -> Result<Response, ContractError> {
+    if *sender != room.player1 {
+        return Err(ContractError::Unauthorized {});
+    }
+    create_challenge(deps, block, room, Expiration::Never {}, payment)
+}
+
+fn check_move_timeout(move_timeout: Option<u64>) -> Result<(), ContractError> {
+    if move_timeout == Some(0) {
+        return Err(ContractError::RoomError {
+            val: "Move timeout has to be at least a second.".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Options every message creating a room takes.
+struct RoomOptions {
+    variant: Variant,
+    board: Option<BoardConfig>,
+    misere: bool,
+    move_timeout: Option<u64>,
+}
+
+/// Builds a room from its options, the one place all of them are checked and applied.
+fn new_room(
     player1: Addr,
-    player2: Addr,
+    player2: Option<Addr>,
+    denom: Denom,
+    stake: Uint128,
+    options: RoomOptions,
+) -> Result<Room, ContractError> {
+    let board = new_board(&options.variant, options.board)?;
+    options.variant.check_misere(options.misere)?;
+    check_move_timeout(options.move_timeout)?;
+    let mut room = Room::new(player1, player2, denom, stake, options.variant, board);
+    room.misere = options.misere;
+    room.move_timeout = options.move_timeout;
+    Ok(room)
+}
+
+fn new_board(variant: &Variant, config: Option<BoardConfig>) -> Result<Board, ContractError> {
+    let config = config.unwrap_or_else(|| variant.default_board());
+    if !config.is_valid() {
+        return Err(ContractError::RoomError {
+            val: format!(
+                "Board must be 3 to {} cells wide and high, 1 or 3 to {} layers deep, with k between 3 and its longest side.",
+                MAX_BOARD_SIZE, MAX_BOARD_DEPTH
+            ),
+        });
+    }
+    variant.check_board(&config)?;
+    Ok(Board::new(&config))
+}
+
+fn create_room(
+    deps: DepsMut,
+    block: &BlockInfo,
+    mut room: Room,
+    payment: Option<Payment>,
 ) -> Result<Response, ContractError> {
-    let room_number = Room::add_room(deps.storage, player1, player2)?;
+    if room.player2.as_ref() == Some(&room.player1) {
+        return Err(ContractError::RoomError {
+            val: "A room needs two different players.".to_string(),
+        });
+    }
+    if let Variant::OrderAndChaos { order } = &room.variant {
+        if !room.is_player(order) {
+            return Err(ContractError::RoomError {
+                val: "Order has to be one of the players.".to_string(),
+            });
+        }
+    }
+    let state = State::load_state(deps.storage)?;
+    if !state.is_accepted_denom(&room.denom) {
+        return Err(ContractError::CustomError {
+            val: format!("Denom {} is not accepted.", denom_name(&room.denom)),
+        });
+    }
+    room.house_fee_bps = state.house_fee_bps;
+    if let Some(payment) = payment {
+        check_stake(&payment, &room)?;
+        room.deposit(&payment.sender)?;
+    }
+    room.start_turn(block);
+    let room_number = Room::add_room(deps.storage, &room)?;
     let response = Response::default();
     let response = response.set_data(to_binary(&room_number)?);
     Ok(response)
 }
 
-pub fn restart_game(deps: DepsMut, room_number: u8) -> Result<Response, ContractError> {
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
+/// Opens a room with the sender in the first seat, their stake has to be attached.
+pub fn create_open_room(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room: Room,
+) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    if payment.is_none() && !room.stake.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "Attach your stake to open a room.".to_string(),
+        });
+    }
+    create_room(deps, &env.block, room, payment)
+}
+
+pub fn join_room(deps: DepsMut, env: Env, info: MessageInfo, room_id: u64) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    join_open_room(deps, &env.block, room_id, info.sender, payment)
+}
+
+fn join_open_room(
+    deps: DepsMut,
+    block: &BlockInfo,
+    room_id: u64,
+    player: Addr,
+    payment: Option<Payment>,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_id, deps.storage)?;
+    if room.is_none() {
         return Err(ContractError::RoomError {
             val: "Room does not exist.".to_string(),
         });
     }
     let mut room = room.unwrap();
 
-    room = room.restart_game();
-    Room::save_room(room_number, deps.storage, room.clone())?;
+    room.join(&player)?;
+    pay_stake(&mut room, &player, payment)?;
+    room.start_turn(block);
+    Room::save_room(room_id, deps.storage, room.clone())?;
 
-    let mut response = Response::default();
-    response = response.set_data(to_binary(&room.board).unwrap());
+    let response = Response::default();
+    let response = response.set_data(to_binary(&room.game_state)?);
     Ok(response)
 }
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::BoardStatus { room_number } => to_binary(&query_table_status(deps, room_number)?),
-        QueryMsg::PlayerTurn { room_number } => to_binary(&query_player_turn(deps, room_number)?),
-        QueryMsg::GameStatus { room_number } => to_binary(&query_game_status(deps, room_number)?),
-        QueryMsg::RoomExist { room_number } => to_binary(&query_room_exist(deps, room_number)?),
-        QueryMsg::RoomList { page_number } => to_binary(&query_list_rooms(deps,page_number)?)
+
+// Deposits the stake of a player who just took the second seat.
+fn pay_stake(room: &mut Room, player: &Addr, payment: Option<Payment>) -> Result<(), ContractError> {
+    match payment {
+        Some(payment) => {
+            check_stake(&payment, room)?;
+            room.deposit(player)
+        }
+        None if !room.stake.is_zero() => Err(ContractError::CustomError {
+            val: "Attach the room's stake to take the seat.".to_string(),
+        }),
+        None => Ok(()),
     }
 }
-pub fn query_table_status(deps: Deps, room_number: u8) -> StdResult<TableStatusResponse> {
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
-        return Err(cosmwasm_std::StdError::NotFound {
-            kind: "Room not found!".to_string(),
+
+/// Calls off a room that hasn't started, each deposit is handed back.
+pub fn cancel_room(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room_id: u64,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_id, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
         });
     }
     let room = room.unwrap();
-    let status = room.board.draw_board();
 
-    Ok(TableStatusResponse { status })
-}
-pub fn query_player_turn(deps: Deps, room_number: u8) -> StdResult<PlayerTurnResponse> {
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
-        return Err(cosmwasm_std::StdError::NotFound {
-            kind: "Room not found!".to_string(),
+    if room.game_state != GameState::WaitingForOpponent {
+        return Err(ContractError::RoomError {
+            val: "Only rooms that haven't started can be cancelled.".to_string(),
         });
     }
-    let room = room.unwrap();
-    Ok(PlayerTurnResponse { turn: room.turn })
-}
-pub fn query_game_status(deps: Deps, room_number: u8) -> StdResult<GameStatusResponse> {
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
-        return Err(cosmwasm_std::StdError::NotFound {
-            kind: "Room not found!".to_string(),
-        });
+    // Deposits go back to whoever made them, so expired challenges can be closed by anyone.
+    if !room.is_player(&info.sender) && !room.is_challenge_expired(&env.block) {
+        return Err(ContractError::Unauthorized {});
     }
-    let room = room.unwrap();
-    let status = String::new();
-
-    Ok(GameStatusResponse { status })
+    close_room(deps.storage, room_id, room)
 }
-pub fn query_list_rooms(deps: Deps, page_number: u8) -> StdResult<ListPageResponse> 
-{
-    let  mut list_rooms: Vec<Option<Room>> = Vec::new();
-    let state = State::load_state(deps.storage);
-    let pages = Pages::new(state.room_count.into(), 10);
-    let page = pages.with_offset(page_number.into());
-    for i in page.start..page.end
-    {
-        let room = Room::load_room(i.try_into().unwrap(), deps.storage);
-        list_rooms.push(room);
+
+pub fn challenge(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room: Room,
+    expires_at: Expiration,
+) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    if payment.is_none() && !room.stake.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "Attach your stake to challenge a player.".to_string(),
+        });
     }
-    Ok(ListPageResponse{
-        list_rooms
-    })
+    create_challenge(deps, &env.block, room, expires_at, payment)
 }
 
-pub fn query_room_exist(deps: Deps, room_number: u8) -> StdResult<RoomExistResponse> {
-    let room = Room::load_room(room_number, deps.storage);
-    if room == None {
-        return Ok(RoomExistResponse { room_exist: false });
+fn create_challenge(
+    deps: DepsMut,
+    block: &BlockInfo,
+    mut room: Room,
+    expires_at: Expiration,
+    payment: Option<Payment>,
+) -> Result<Response, ContractError> {
+    if room.player2.as_ref() == Some(&room.player1) {
+        return Err(ContractError::RoomError {
+            val: "You can't challenge yourself.".to_string(),
+        });
     }
-    Ok(RoomExistResponse { room_exist: true })
+    if expires_at.is_expired(block) {
+        return Err(ContractError::RoomError {
+            val: "The challenge would already be expired.".to_string(),
+        });
+    }
+    room.challenge(expires_at);
+    create_room(deps, block, room, payment)
 }
 
-#[cfg(test)]
-mod tests {
+pub fn accept_challenge(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room_id: u64,
+) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    accept(deps, &env.block, room_id, info.sender, payment)
+}
 
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
-    use cosmwasm_std::{coins, Addr, MemoryStorage, OwnedDeps};
-    fn init() -> OwnedDeps<MemoryStorage, MockApi, MockQuerier>
-    {
-        let mut deps = mock_dependencies();
-        let res = State::init_state(&mut deps.storage);
-        assert!(res.is_ok());
-        deps
+fn accept(
+    deps: DepsMut,
+    block: &BlockInfo,
+    room_id: u64,
+    player: Addr,
+    payment: Option<Payment>,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_id, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
     }
+    let mut room = room.unwrap();
 
-    fn add_room_init() -> OwnedDeps<MemoryStorage, MockApi, MockQuerier> {
-        let mut deps= init();
-        let info = mock_info("info", &coins(10, "ioc"));
+    room.accept_challenge(&player, block)?;
+    pay_stake(&mut room, &player, payment)?;
+    room.start_turn(block);
+    Room::save_room(room_id, deps.storage, room.clone())?;
 
-        let msg = HandleMsg::AddRoom {
-            player1: Addr::unchecked("player1"),
-            player2: Addr::unchecked("player2"),
-        };
-        let room = execute(deps.as_mut(), mock_env(), info, msg);
-        assert!(room.is_ok());
-        deps
-    }
+    let response = Response::default();
+    let response = response.set_data(to_binary(&room.game_state)?);
+    Ok(response)
+}
 
-    #[test]
-    fn add_room_test() {
-        add_room_init();
+pub fn decline_challenge(deps: DepsMut, info: MessageInfo, room_id: u64) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_id, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
     }
+    let room = room.unwrap();
 
-    #[test]
-    fn player_turn_query_test() {
-        let deps = add_room_init();
-        query(deps.as_ref(), mock_env(), QueryMsg::PlayerTurn {  room_number: 0 }).unwrap();
+    if room.challenge_expires.is_none() || room.game_state != GameState::WaitingForOpponent {
+        return Err(ContractError::RoomError {
+            val: "No challenge is pending.".to_string(),
+        });
     }
-    #[test]
-    fn room_exist_query_test() {
-        let deps = add_room_init();
-        query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::RoomExist {
-             room_number:0,
-            },
-        )
-        .unwrap();
+    if Some(&info.sender) != room.player2.as_ref() {
+        return Err(ContractError::Unauthorized {});
     }
+    close_room(deps.storage, room_id, room)
+}
 
-    #[test]
-    fn table_status_query_test() {
-        let deps = add_room_init();
+/// Abandons a room that never started and refunds its deposits.
+fn close_room(storage: &mut dyn Storage, room_id: u64, mut room: Room) -> Result<Response, ContractError> {
+    let mut response = Response::default();
+    response.messages = refund_deposits(&room)?;
+    room.total_coins_raised = Uint128::zero();
+    room.challenge_expires = None;
+    room.game_state = GameState::Abandoned;
+    Room::save_room(room_id, storage, room.clone())?;
 
-        query(deps.as_ref(), mock_env(), QueryMsg::BoardStatus { room_number: 0 }).unwrap();
-    }
+    response = response.set_data(to_binary(&room.game_state)?);
+    Ok(response)
+}
 
-    #[test]
-    fn game_status_query_test() {
+pub fn enqueue_for_match(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom: Denom,
+    stake: Uint128,
+    variant: Variant,
+    move_timeout: Option<u64>,
+) -> Result<Response, ContractError> {
+    let payment = native_payment(&info)?;
+    if payment.is_none() && !stake.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "Attach your stake to join the queue.".to_string(),
+        });
+    }
+    let entry = QueueEntry {
+        player: info.sender,
+        denom,
+        stake,
+        variant,
+        move_timeout,
+        enqueued_at: env.block.time,
+    };
+    enqueue(deps, &env.block, entry, payment)
+}
+
+/// Pairs `entry` with the oldest compatible one into a new room, or queues it.
+/// Responds with the new room's number, `None` if the player has to wait.
+fn enqueue(
+    deps: DepsMut,
+    block: &BlockInfo,
+    entry: QueueEntry,
+    payment: Option<Payment>,
+) -> Result<Response, ContractError> {
+    check_move_timeout(entry.move_timeout)?;
+    if let Variant::OrderAndChaos { .. } = entry.variant {
+        return Err(ContractError::CustomError {
+            val: "Order and Chaos rooms can't be matched from the queue.".to_string(),
+        });
+    }
+    new_board(&entry.variant, None)?;
+    let state = State::load_state(deps.storage)?;
+    if !state.is_accepted_denom(&entry.denom) {
+        return Err(ContractError::CustomError {
+            val: format!("Denom {} is not accepted.", denom_name(&entry.denom)),
+        });
+    }
+    if let Some(payment) = &payment {
+        check_payment(payment, &entry.denom, entry.stake)?;
+    }
+    if QueueEntry::load_by_player(deps.storage, &entry.player)?.is_some() {
+        return Err(ContractError::CustomError {
+            val: "You are already waiting in the queue.".to_string(),
+        });
+    }
+
+    let room_number = match entry.find_match(deps.storage)? {
+        Some((entry_id, waiting)) => {
+            QueueEntry::remove(deps.storage, entry_id)?;
+            let options = RoomOptions {
+                variant: entry.variant,
+                board: None,
+                misere: false,
+                move_timeout: entry.move_timeout,
+            };
+            let mut room = new_room(waiting.player, Some(entry.player), entry.denom, entry.stake, options)?;
+            if !room.stake.is_zero() {
+                let (player1, player2) = (room.player1.clone(), room.opponent(&room.player1));
+                room.deposit(&player1)?;
+                room.deposit(&player2)?;
+            }
+            room.house_fee_bps = state.house_fee_bps;
+            room.start_turn(block);
+            Some(Room::add_room(deps.storage, &room)?)
+        }
+        None => {
+            entry.enqueue(deps.storage)?;
+            None
+        }
+    };
+    let response = Response::default();
+    let response = response.set_data(to_binary(&room_number)?);
+    Ok(response)
+}
+
+pub fn leave_queue(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let (entry_id, entry) = match QueueEntry::load_by_player(deps.storage, &info.sender)? {
+        Some(queued) => queued,
+        None => {
+            return Err(ContractError::CustomError {
+                val: "You are not waiting in the queue.".to_string(),
+            })
+        }
+    };
+    QueueEntry::remove(deps.storage, entry_id)?;
+
+    let mut response = Response::default();
+    if !entry.stake.is_zero() {
+        response = response.add_submessage(send_tokens(&entry.player, entry.stake, &entry.denom)?);
+    }
+    Ok(response)
+}
+
+pub fn deposit(deps: DepsMut, env: Env, info: MessageInfo, room_number: u64) -> Result<Response, ContractError> {
+    match native_payment(&info)? {
+        Some(payment) => deposit_stake(deps, &env.block, room_number, payment),
+        None => Err(ContractError::CustomError {
+            val: "No coins sent".to_string(),
+        }),
+    }
+}
+
+fn deposit_stake(
+    deps: DepsMut,
+    block: &BlockInfo,
+    room_number: u64,
+    payment: Payment,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
+    }
+    let mut room = room.unwrap();
+
+    check_stake(&payment, &room)?;
+    room.deposit(&payment.sender)?;
+    room.start_turn(block);
+    Room::save_room(room_number, deps.storage, room.clone())?;
+
+    let response = Response::default();
+    let response = response.set_data(to_binary(&room.game_state)?);
+    Ok(response)
+}
+
+/// Handles CW20 tokens sent to the contract, `info.sender` is the token contract.
+pub fn receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let payment = Payment {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        denom: Denom::Cw20(info.sender),
+        amount: wrapper.amount,
+    };
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::AddRoom {
+            player1,
+            player2,
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let denom = payment.denom.clone();
+            let room = new_room(player1, Some(player2), denom, payment.amount, options)?;
+            let sender = payment.sender.clone();
+            add_seated_room(deps, &env.block, &sender, room, Some(payment))
+        }
+        ReceiveMsg::Deposit { room_number } => deposit_stake(deps, &env.block, room_number, payment),
+        ReceiveMsg::CreateRoom {
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let (sender, denom) = (payment.sender.clone(), payment.denom.clone());
+            let room = new_room(sender, None, denom, payment.amount, options)?;
+            create_room(deps, &env.block, room, Some(payment))
+        }
+        ReceiveMsg::JoinRoom { room_id } => {
+            let player = payment.sender.clone();
+            join_open_room(deps, &env.block, room_id, player, Some(payment))
+        }
+        ReceiveMsg::Challenge {
+            opponent,
+            expires_at,
+            variant,
+            board,
+            misere,
+            move_timeout,
+        } => {
+            let options = RoomOptions {
+                variant,
+                board,
+                misere,
+                move_timeout,
+            };
+            let (sender, denom) = (payment.sender.clone(), payment.denom.clone());
+            let opponent = deps.api.addr_validate(&opponent)?;
+            let room = new_room(sender, Some(opponent), denom, payment.amount, options)?;
+            create_challenge(deps, &env.block, room, expires_at, Some(payment))
+        }
+        ReceiveMsg::AcceptChallenge { room_id } => {
+            let player = payment.sender.clone();
+            accept(deps, &env.block, room_id, player, Some(payment))
+        }
+        ReceiveMsg::EnqueueForMatch {
+            variant,
+            move_timeout,
+        } => {
+            let entry = QueueEntry {
+                player: payment.sender.clone(),
+                denom: payment.denom.clone(),
+                stake: payment.amount,
+                variant,
+                move_timeout,
+                enqueued_at: env.block.time,
+            };
+            enqueue(deps, &env.block, entry, Some(payment))
+        }
+    }
+}
+
+pub fn resign(deps: DepsMut, info: MessageInfo, room_number: u64) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
+    }
+    let mut room = room.unwrap();
+
+    if !room.is_player(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if room.game_state != GameState::InProgress {
+        return Err(ContractError::CustomError {
+            val: "Game is not in progress.".to_string(),
+        });
+    }
+    room.game_state = GameState::Resigned {
+        player: info.sender.clone(),
+    };
+    let winner = room.opponent(&info.sender);
+
+    let mut response = Response::default();
+    response.messages = pay_winner(deps.storage, &room, &winner)?;
+    room.total_coins_raised = Uint128::zero();
+    Room::save_room(room_number, deps.storage, room.clone())?;
+
+    response = response.set_data(to_binary(&room.game_state)?);
+    Ok(response)
+}
+
+pub fn claim_timeout(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room_id: u64,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_id, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
+    }
+    let mut room = room.unwrap();
+
+    if !room.is_player(&info.sender) || info.sender == room.turn {
+        return Err(ContractError::Unauthorized {});
+    }
+    match room.move_deadline() {
+        Some(deadline) if env.block.time >= deadline => {}
+        Some(_) => {
+            return Err(ContractError::CustomError {
+                val: "Your opponent still has time to move.".to_string(),
+            })
+        }
+        None => {
+            return Err(ContractError::CustomError {
+                val: "No move is being timed in this room.".to_string(),
+            })
+        }
+    }
+    room.game_state = GameState::TimedOut {
+        player: room.turn.clone(),
+    };
+
+    let mut response = Response::default();
+    response.messages = pay_winner(deps.storage, &room, &info.sender)?;
+    room.total_coins_raised = Uint128::zero();
+    Room::save_room(room_id, deps.storage, room.clone())?;
+
+    response = response.set_data(to_binary(&room.game_state)?);
+    Ok(response)
+}
+
+pub fn restart_game(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    room_number: u64,
+) -> Result<Response, ContractError> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(ContractError::RoomError {
+            val: "Room does not exist.".to_string(),
+        });
+    }
+    let mut room = room.unwrap();
+
+    if !room.is_player(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    // Restarting a running game would strand the escrowed stakes.
+    if !room.is_finished() {
+        return Err(ContractError::CustomError {
+            val: "Game is not finished yet.".to_string(),
+        });
+    }
+
+    room = room.restart_game();
+    room.start_turn(&env.block);
+    Room::save_room(room_number, deps.storage, room.clone())?;
+
+    let mut response = Response::default();
+    response = response.set_data(to_binary(&room.board).unwrap());
+    Ok(response)
+}
+pub fn withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: Denom,
+    amount: Uint128,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let mut state = State::load_state(deps.storage)?;
+    if info.sender != state.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if amount.is_zero() {
+        return Err(ContractError::StateError {
+            val: "Nothing to withdraw.".to_string(),
+        });
+    }
+
+    state.take_from_treasury(&denom, amount)?;
+    state.save_state(deps.storage)?;
+
+    let mut response = Response::default();
+    response.messages.push(send_tokens(&recipient, amount, &denom)?);
+    Ok(response)
+}
+
+pub fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    admin: Option<String>,
+    house_fee_bps: Option<u16>,
+    accepted_denoms: Option<Vec<String>>,
+    accepted_cw20: Option<Vec<String>>,
+) -> Result<Response, ContractError> {
+    let mut state = State::load_state(deps.storage)?;
+    if info.sender != state.admin {
+        return Err(ContractError::Unauthorized {});
+    }
+    if let Some(admin) = admin {
+        state.admin = deps.api.addr_validate(&admin)?;
+    }
+    if let Some(house_fee_bps) = house_fee_bps {
+        check_house_fee(house_fee_bps)?;
+        state.house_fee_bps = house_fee_bps;
+    }
+    if let Some(accepted_denoms) = accepted_denoms {
+        state.accepted_denoms = accepted_denoms;
+    }
+    if let Some(accepted_cw20) = accepted_cw20 {
+        state.accepted_cw20 = validate_addresses(deps.as_ref(), &accepted_cw20)?;
+    }
+    check_accepted_denoms(&state)?;
+    state.save_state(deps.storage)?;
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::BoardStatus { room_number } => to_binary(&query_table_status(deps, room_number)?),
+        QueryMsg::PlayerTurn { room_number } => to_binary(&query_player_turn(deps, room_number)?),
+        QueryMsg::GameStatus { room_number } => to_binary(&query_game_status(deps, room_number)?),
+        QueryMsg::RoomExist { room_number } => to_binary(&query_room_exist(deps, room_number)?),
+        QueryMsg::RoomList {
+            start_after,
+            limit,
+            status_filter,
+        } => to_binary(&query_list_rooms(deps, start_after, limit, status_filter)?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::RoomsByPlayer {
+            player,
+            start_after,
+            limit,
+            status_filter,
+        } => to_binary(&query_rooms_by_player(deps, player, start_after, limit, status_filter)?),
+        QueryMsg::QueueStatus { player } => to_binary(&query_queue_status(deps, player)?),
+    }
+}
+pub fn query_table_status(deps: Deps, room_number: u64) -> StdResult<TableStatusResponse> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(cosmwasm_std::StdError::NotFound {
+            kind: "Room not found!".to_string(),
+        });
+    }
+    let room = room.unwrap();
+    let status = room.draw_board();
+
+    Ok(TableStatusResponse { status })
+}
+pub fn query_player_turn(deps: Deps, room_number: u64) -> StdResult<PlayerTurnResponse> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(cosmwasm_std::StdError::NotFound {
+            kind: "Room not found!".to_string(),
+        });
+    }
+    let room = room.unwrap();
+    Ok(PlayerTurnResponse { turn: room.turn })
+}
+pub fn query_game_status(deps: Deps, room_number: u64) -> StdResult<GameStatusResponse> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Err(cosmwasm_std::StdError::NotFound {
+            kind: "Room not found!".to_string(),
+        });
+    }
+    let room = room.unwrap();
+    let winning_line = match &room.game_state {
+        GameState::Won { line, .. } => Some(line.clone()),
+        _ => None,
+    };
+    let turn = if room.is_finished() {
+        None
+    } else {
+        Some(room.turn.clone())
+    };
+
+    Ok(GameStatusResponse {
+        winner: room.winner(),
+        move_deadline: room.move_deadline(),
+        winning_line,
+        no_moves: room.no_moves,
+        turn,
+        pot: room.total_coins_raised,
+        last_move: room.last_move,
+        next_board: room.next_board,
+        denom: room.denom,
+        game_state: room.game_state,
+        variant: room.variant,
+        misere: room.misere,
+        challenge_expires: room.challenge_expires,
+    })
+}
+pub fn query_list_rooms(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    status_filter: Option<RoomStatus>,
+) -> StdResult<RoomsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut list = vec![];
+    let mut next = None;
+    let mut range = rooms()
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .peekable();
+    let mut scanned = 0;
+    while let Some(item) = range.next() {
+        let (room_id, room) = item?;
+        scanned += 1;
+        if matches_status(&room, &status_filter) {
+            list.push(RoomInfo { room_id, room });
+        }
+        if list.len() == limit || scanned == MAX_SCAN {
+            // Only hand out a cursor when there are rooms left to scan.
+            next = range.peek().is_some().then_some(room_id);
+            break;
+        }
+    }
+    Ok(RoomsResponse { rooms: list, next })
+}
+
+fn matches_status(room: &Room, status_filter: &Option<RoomStatus>) -> bool {
+    match status_filter {
+        Some(status) => room.status() == *status,
+        None => true,
+    }
+}
+
+pub fn query_rooms_by_player(
+    deps: Deps,
+    player: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    status_filter: Option<RoomStatus>,
+) -> StdResult<RoomsResponse> {
+    let player = deps.api.addr_validate(&player)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let rooms = rooms();
+
+    // Only keys are read from the seat indexes, rooms are loaded until the page is full.
+    let mut as_player1 = rooms
+        .idx
+        .player1
+        .prefix(player.clone())
+        .keys(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .peekable();
+    let mut as_player2 = rooms
+        .idx
+        .player2
+        .prefix(player)
+        .keys(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .peekable();
+
+    let mut list = vec![];
+    let mut next = None;
+    for scanned in 1.. {
+        // Both seats list room ids in ascending order, take the lower one next.
+        let room_id = match (as_player1.peek(), as_player2.peek()) {
+            (Some(Ok(first)), Some(Ok(second))) if first > second => as_player2.next(),
+            (Some(_), _) => as_player1.next(),
+            (None, _) => as_player2.next(),
+        };
+        let room_id = match room_id {
+            Some(room_id) => room_id?,
+            None => break,
+        };
+        // Migrated rooms can seat the same player twice.
+        if matches!(as_player2.peek(), Some(Ok(next)) if *next == room_id) {
+            as_player2.next();
+        }
+        let room = rooms.load(deps.storage, room_id)?;
+        if matches_status(&room, &status_filter) {
+            list.push(RoomInfo { room_id, room });
+        }
+        if list.len() == limit || scanned == MAX_SCAN {
+            next = (as_player1.peek().is_some() || as_player2.peek().is_some()).then_some(room_id);
+            break;
+        }
+    }
+    Ok(RoomsResponse { rooms: list, next })
+}
+
+pub fn query_queue_status(deps: Deps, player: String) -> StdResult<QueueStatusResponse> {
+    let player = deps.api.addr_validate(&player)?;
+    let entry = QueueEntry::load_by_player(deps.storage, &player)?.map(|(_, entry)| entry);
+    Ok(QueueStatusResponse { entry })
+}
+
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let state = State::load_state(deps.storage)?;
+    Ok(ConfigResponse {
+        admin: state.admin,
+        house_fee_bps: state.house_fee_bps,
+        accepted_denoms: state.accepted_denoms,
+        accepted_cw20: state.accepted_cw20,
+        treasury: state.treasury,
+    })
+}
+
+pub fn query_room_exist(deps: Deps, room_number: u64) -> StdResult<RoomExistResponse> {
+    let room = Room::load_room(room_number, deps.storage)?;
+    if room.is_none() {
+        return Ok(RoomExistResponse { room_exist: false });
+    }
+    Ok(RoomExistResponse { room_exist: true })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cell::Coordinates;
+    use crate::state::TreasuryBalance;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
+    use cosmwasm_std::{coins, Addr, CosmosMsg, MemoryStorage, OwnedDeps};
+    fn init() -> OwnedDeps<MemoryStorage, MockApi, MockQuerier>
+    {
+        let mut deps = mock_dependencies();
+        let msg = InitMsg {
+            admin: None,
+            house_fee_bps: 0,
+            accepted_denoms: vec!["ioc".to_string(), "uatom".to_string()],
+            accepted_cw20: vec!["token".to_string()],
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        assert!(res.is_ok());
+        deps
+    }
+
+    fn add_room_init() -> OwnedDeps<MemoryStorage, MockApi, MockQuerier> {
+        let mut deps= init();
+        start_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+        deps
+    }
+
+    /// Player1 invites player2 to a room staked in ioc, without depositing anything.
+    fn add_test_room(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        variant: Variant,
+        board: Option<BoardConfig>,
+        stake: u128,
+        misere: bool,
+        move_timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(stake),
+            variant,
+            board,
+            misere,
+            move_timeout,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg)
+    }
+
+    /// Adds a room like `add_test_room`, then player1 deposits and player2 accepts it with their stake.
+    fn start_test_room(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        variant: Variant,
+        board: Option<BoardConfig>,
+        stake: u128,
+        misere: bool,
+        move_timeout: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        let res = add_test_room(deps, variant, board, stake, misere, move_timeout)?;
+        let room_id: u64 = from_binary(res.data.as_ref().unwrap()).unwrap();
+        if stake != 0 {
+            let info = mock_info("player1", &coins(stake, "ioc"));
+            execute(deps.as_mut(), mock_env(), info, HandleMsg::Deposit { room_number: room_id }).unwrap();
+        }
+        accept_room(deps, "player2", room_id, stake);
+        Ok(res)
+    }
+
+    fn accept_room(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        player: &str,
+        room_id: u64,
+        stake: u128,
+    ) {
+        let funds = if stake == 0 { vec![] } else { coins(stake, "ioc") };
+        let msg = HandleMsg::AcceptChallenge { room_id };
+        execute(deps.as_mut(), mock_env(), mock_info(player, &funds), msg).unwrap();
+    }
+
+    fn add_free_room(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        player1: &str,
+        player2: &str,
+    ) {
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked(player1),
+            player2: Addr::unchecked(player2),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::zero(),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(player1, &[]), msg).unwrap();
+        accept_room(deps, player2, from_binary(&res.data.unwrap()).unwrap(), 0);
+    }
+
+    fn open_room(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>, stake: u128) {
+        let msg = HandleMsg::CreateRoom {
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(stake),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let funds = if stake == 0 { vec![] } else { coins(stake, "ioc") };
+        execute(deps.as_mut(), mock_env(), mock_info("player1", &funds), msg).unwrap();
+    }
+
+    fn send_challenge(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        expires_at: Expiration,
+    ) -> Result<Response, ContractError> {
+        let msg = HandleMsg::Challenge {
+            opponent: "player2".to_string(),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            expires_at,
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("player1", &coins(10, "ioc")), msg)
+    }
+
+    fn play_moves(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        all_coordinates: &[Coordinates],
+    ) -> Vec<Result<Response, ContractError>> {
+        let actions = all_coordinates
+            .iter()
+            .map(|coordinates| Move::Place { coordinates: coordinates.clone() })
+            .collect::<Vec<_>>();
+        play_actions(deps, &actions)
+    }
+
+    // Plays the moves in room 0, alternating between player1 and player2.
+    fn play_actions(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        actions: &[Move],
+    ) -> Vec<Result<Response, ContractError>> {
+        let player1_info = mock_info("player1", &[]);
+        let player2_info = mock_info("player2", &[]);
+        let mut results = vec![];
+        for (i, action) in actions.iter().enumerate() {
+            let msg = HandleMsg::PlayMove {
+                room_number: 0,
+                action: action.clone(),
+            };
+            let info = if i % 2 == 0 { player1_info.clone() } else { player2_info.clone() };
+            results.push(execute(deps.as_mut(), mock_env(), info, msg));
+        }
+        results
+    }
+
+    fn bank_send(to_address: &str, amount: u128) -> SubMsg {
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: to_address.to_string(),
+            amount: coins(amount, "ioc"),
+        }))
+    }
+
+    #[test]
+    fn add_room_test() {
+        add_room_init();
+    }
+
+    #[test]
+    fn player_turn_query_test() {
+        let deps = add_room_init();
+        query(deps.as_ref(), mock_env(), QueryMsg::PlayerTurn {  room_number: 0 }).unwrap();
+    }
+    #[test]
+    fn room_exist_query_test() {
+        let deps = add_room_init();
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::RoomExist {
+             room_number:0,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn table_status_query_test() {
         let deps = add_room_init();
 
-        query(deps.as_ref(), mock_env(), QueryMsg::GameStatus { room_number: 0 }).unwrap();
+        query(deps.as_ref(), mock_env(), QueryMsg::BoardStatus { room_number: 0 }).unwrap();
+    }
+
+    fn game_status(deps: &OwnedDeps<MemoryStorage, MockApi, MockQuerier>) -> GameStatusResponse {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GameStatus { room_number: 0 }).unwrap();
+        from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn game_status_query_test() {
+        let mut deps = add_room_init();
+
+        let status = game_status(&deps);
+        assert_eq!(status.game_state, GameState::InProgress);
+        assert_eq!(status.turn, Some(Addr::unchecked("player1")));
+        assert_eq!(status.pot, Uint128::new(20));
+        assert_eq!(status.no_moves, 0);
+        assert_eq!(status.last_move, None);
+
+        play_moves(&mut deps, &[Coordinates::new(1, 1)]);
+        let status = game_status(&deps);
+        assert_eq!(status.turn, Some(Addr::unchecked("player2")));
+        assert_eq!(status.no_moves, 1);
+        assert_eq!(status.last_move, Some(LastMove::from(&mock_env().block)));
+        assert_eq!(status.winner, None);
+    }
+
+    #[test]
+    fn finished_game_status_query_test() {
+        let mut deps = add_room_init();
+        play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(1, 0),
+                Coordinates::new(2, 2),
+                Coordinates::new(2, 0),
+            ],
+        );
+
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(status.winning_line.unwrap().len(), 3);
+        assert_eq!(status.turn, None);
+        assert_eq!(status.no_moves, 5);
+        assert_eq!(status.denom, Denom::Native("ioc".to_string()));
+    }
+
+    #[test]
+    fn play_move_test() {
+        let mut deps = add_room_init();
+
+        let coordinates = Coordinates::new(1, 1);
+
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates } };
+        let player1_info = mock_info("player1", &[]);
+        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg);
+
+        assert!(play_move.is_ok());
+    }
+
+    #[test]
+    fn play_move_with_coins_test() {
+        let mut deps = add_room_init();
+
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates: Coordinates::new(1, 1) } };
+        let player1_info = mock_info("player1", &coins(10, "ioc"));
+        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg);
+
+        assert!(play_move.is_err());
+    }
+
+    #[test]
+    fn play_before_deposits_test() {
+        let mut deps = init();
+        add_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+
+        let info = mock_info("player1", &coins(10, "ioc"));
+        execute(deps.as_mut(), mock_env(), info, HandleMsg::Deposit { room_number: 0 }).unwrap();
+
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates: Coordinates::new(1, 1) } };
+        let play_move = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg);
+        assert!(play_move.is_err());
+    }
+
+    #[test]
+    fn invalid_deposit_test() {
+        let mut deps = init();
+        add_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+
+        let deposit = HandleMsg::Deposit { room_number: 0 };
+        let wrong_amount = mock_info("player1", &coins(5, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), wrong_amount, deposit.clone()).is_err());
+
+        let wrong_denom = mock_info("player1", &coins(10, "uatom"));
+        assert!(execute(deps.as_mut(), mock_env(), wrong_denom, deposit.clone()).is_err());
+
+        let not_a_player = mock_info("anyone", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), not_a_player, deposit.clone()).is_err());
+
+        let player1_info = mock_info("player1", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), player1_info.clone(), deposit.clone()).is_ok());
+        assert!(execute(deps.as_mut(), mock_env(), player1_info, deposit).is_err());
+    }
+
+    #[test]
+    fn play_same_move_test() {
+        let mut deps = add_room_init();
+
+        let coordinates = Coordinates::new(1, 1);
+
+        let results = play_moves(&mut deps, &[coordinates.clone(), coordinates]);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err())
+    }
+
+    #[test]
+    fn play_same_player_test() {
+        let mut deps = add_room_init();
+
+        let coordinates = Coordinates::new(1, 1);
+        let msg = HandleMsg::PlayMove {
+            room_number: 0,
+            action: Move::Place { coordinates },
+        };
+        let player1_info = mock_info("player1", &[]);
+        let play_move = execute(deps.as_mut(), mock_env(), player1_info.clone(), msg);
+        assert!(play_move.is_ok());
+
+        let coordinates = Coordinates::new(1, 2);
+        let msg = HandleMsg::PlayMove {
+            room_number: 0,
+            action: Move::Place { coordinates },
+        };
+        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg);
+        assert!(play_move.is_err());
+    }
+    #[test]
+    fn restart_game() {
+        let mut deps = add_room_init();
+
+        let results = play_moves(&mut deps, &[Coordinates::new(1, 1), Coordinates::new(2, 1)]);
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        // The stakes are still in escrow, so the game can't be restarted yet.
+        let restart = HandleMsg::RestartGame { room_number: 0};
+        let player1_info = mock_info("player1", &[]);
+        let restart_game = execute(deps.as_mut(), mock_env(), player1_info.clone(), restart.clone());
+        assert!(restart_game.is_err());
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let outsider = mock_info("anyone", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), outsider, restart.clone()).is_err());
+
+        let restart_game = execute(deps.as_mut(), mock_env(), player1_info, restart);
+        assert!(restart_game.is_ok());
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.game_state, GameState::WaitingForOpponent);
+        assert_eq!(room.total_coins_raised, Uint128::zero());
+        assert!(room.board.cells.iter().all(|cell| cell.player.is_none()));
+    }
+    #[test]
+    fn play_game_in_not_existing_room_test() {
+        let mut deps = add_room_init();
+
+        let player1_info = mock_info("player1", &[]);
+
+        let coordinates = Coordinates::new(2, 0);
+        let msg = HandleMsg::PlayMove {
+            room_number: 1,
+            action: Move::Place { coordinates },
+        };
+        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg);
+        assert!(play_move.is_err())
+    }
+
+    #[test]
+    fn play_winning_game_test() {
+        let mut deps = add_room_init();
+
+        let all_coordinates: Vec<Coordinates> = vec![
+            Coordinates::new(0, 0),
+            Coordinates::new(0, 1),
+            Coordinates::new(1, 0),
+            Coordinates::new(1, 1),
+            Coordinates::new(2, 0),
+            Coordinates::new(2, 1),
+        ];
+
+        let results = play_moves(&mut deps, &all_coordinates);
+        for (i, play_move) in results.iter().enumerate() {
+            if i != 5 {
+                assert!(play_move.is_ok())
+            } else {
+                assert!(play_move.is_err());
+            }
+        }
+        // Winner takes the whole pot.
+        let winning_move = results[4].as_ref().unwrap();
+        assert_eq!(winning_move.messages, vec![bank_send("player1", 20)]);
+        assert_eq!(game_status(&deps).pot, Uint128::zero());
+    }
+
+    #[test]
+    fn play_tie_game_test() {
+        let mut deps = add_room_init();
+
+        let all_coordinates: Vec<Coordinates> = vec![
+            Coordinates::new(0, 0),
+            Coordinates::new(0, 1),
+            Coordinates::new(0, 2),
+            Coordinates::new(2, 0),
+            Coordinates::new(2, 1),
+            Coordinates::new(2, 2),
+            Coordinates::new(1, 0),
+            Coordinates::new(1, 1),
+            Coordinates::new(1, 2),
+        ];
+
+        let results = play_moves(&mut deps, &all_coordinates);
+        assert!(results.iter().all(|res| res.is_ok()));
+        // Both players get their stake back.
+        let last_move = results[8].as_ref().unwrap();
+        assert_eq!(
+            last_move.messages,
+            vec![bank_send("player1", 10), bank_send("player2", 10)]
+        );
+
+        let msg = HandleMsg::PlayMove {
+            room_number: 0,
+            action: Move::Place { coordinates: all_coordinates[0].clone() },
+        };
+        let player2_info = mock_info("player2", &[]);
+        let play_move = execute(deps.as_mut(), mock_env(), player2_info, msg);
+        assert!(play_move.is_err());
+    }
+
+    #[test]
+    fn instantiate_without_denoms_test() {
+        let mut deps = mock_dependencies();
+        let msg = InitMsg {
+            admin: None,
+            house_fee_bps: 0,
+            accepted_denoms: vec![],
+            accepted_cw20: vec![],
+        };
+        let res = instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn add_room_with_unaccepted_denom_test() {
+        let mut deps = init();
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            denom: Denom::Native("uosmo".to_string()),
+            stake: Uint128::new(10),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn payout_in_room_denom_test() {
+        let mut deps = init();
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            denom: Denom::Native("uatom".to_string()),
+            stake: Uint128::new(7),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let info = mock_info("player1", &coins(7, "uatom"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let accept = HandleMsg::AcceptChallenge { room_id: 0 };
+        let ioc_deposit = mock_info("player2", &coins(7, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), ioc_deposit, accept.clone()).is_err());
+        let info = mock_info("player2", &coins(7, "uatom"));
+        execute(deps.as_mut(), mock_env(), info, accept).unwrap();
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(
+            results[4].as_ref().unwrap().messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "player1".to_string(),
+                amount: coins(14, "uatom"),
+            })]
+        );
+    }
+
+    fn cw20_send(sender: &str, amount: u128, msg: &ReceiveMsg) -> HandleMsg {
+        HandleMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(msg).unwrap(),
+        })
+    }
+
+    #[test]
+    fn third_party_add_room_test() {
+        let mut deps = init();
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::zero(),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), msg);
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+
+        let add_room = ReceiveMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let msg = cw20_send("anyone", 25, &add_room);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg);
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        assert!(Room::load_room(0, &deps.storage).unwrap().is_none());
+    }
+
+    #[test]
+    fn add_room_needs_acceptance_test() {
+        let mut deps = init();
+        add_test_room(&mut deps, Variant::Classic, None, 0, false, None).unwrap();
+        // Nothing is at stake, but player2 hasn't agreed to play yet.
+        assert_eq!(game_status(&deps).game_state, GameState::WaitingForOpponent);
+        assert!(play_moves(&mut deps, &[Coordinates::new(1, 1)])[0].is_err());
+        let accept = HandleMsg::AcceptChallenge { room_id: 0 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), accept);
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        accept_room(&mut deps, "player2", 0, 0);
+        assert_eq!(game_status(&deps).game_state, GameState::InProgress);
+
+        // Player2 can turn the room down instead.
+        add_test_room(&mut deps, Variant::Classic, None, 0, false, None).unwrap();
+        let msg = HandleMsg::DeclineChallenge { room_id: 1 };
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).unwrap();
+        let room = Room::load_room(1, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.game_state, GameState::Abandoned);
+    }
+
+    #[test]
+    fn cw20_room_test() {
+        let mut deps = init();
+        let add_room = ReceiveMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let msg = cw20_send("player1", 25, &add_room);
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.denom, Denom::Cw20(Addr::unchecked("token")));
+        assert_eq!(room.stake, Uint128::new(25));
+
+        // Native coins can't fund a CW20 room.
+        let native = mock_info("player2", &coins(25, "ioc"));
+        let accept = HandleMsg::AcceptChallenge { room_id: 0 };
+        assert!(execute(deps.as_mut(), mock_env(), native, accept).is_err());
+
+        let accept = ReceiveMsg::AcceptChallenge { room_id: 0 };
+        let wrong_amount = cw20_send("player2", 20, &accept);
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("token", &[]), wrong_amount).is_err());
+        let msg = cw20_send("player2", 25, &accept);
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(
+            results[4].as_ref().unwrap().messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "player1".to_string(),
+                    amount: Uint128::new(50),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn unaccepted_cw20_test() {
+        let mut deps = init();
+        let add_room = ReceiveMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player2"),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let msg = cw20_send("player1", 25, &add_room);
+        let res = execute(deps.as_mut(), mock_env(), mock_info("other_token", &[]), msg);
+        assert!(res.is_err());
+    }
+
+    fn set_house_fee(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>, house_fee_bps: u16) {
+        let msg = HandleMsg::UpdateConfig {
+            admin: None,
+            house_fee_bps: Some(house_fee_bps),
+            accepted_denoms: None,
+            accepted_cw20: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn house_fee_test() {
+        let mut deps = init();
+        set_house_fee(&mut deps, 500);
+        start_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+        // Raising the fee doesn't reach games that already started.
+        set_house_fee(&mut deps, MAX_HOUSE_FEE_BPS);
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(results[4].as_ref().unwrap().messages, vec![bank_send("player1", 19)]);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.admin, Addr::unchecked("creator"));
+        assert_eq!(config.house_fee_bps, MAX_HOUSE_FEE_BPS);
+        assert_eq!(
+            config.treasury,
+            vec![TreasuryBalance {
+                denom: Denom::Native("ioc".to_string()),
+                amount: Uint128::new(1),
+            }]
+        );
+    }
+
+    #[test]
+    fn tie_is_not_charged_test() {
+        let mut deps = init();
+        set_house_fee(&mut deps, 500);
+        start_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(0, 1),
+                Coordinates::new(0, 2),
+                Coordinates::new(2, 0),
+                Coordinates::new(2, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(1, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(1, 2),
+            ],
+        );
+        assert_eq!(
+            results[8].as_ref().unwrap().messages,
+            vec![bank_send("player1", 10), bank_send("player2", 10)]
+        );
+    }
+
+    #[test]
+    fn withdraw_test() {
+        let mut deps = init();
+        set_house_fee(&mut deps, MAX_HOUSE_FEE_BPS);
+        start_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+        play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+
+        let withdraw = |amount: u128| HandleMsg::Withdraw {
+            denom: Denom::Native("ioc".to_string()),
+            amount: Uint128::new(amount),
+            recipient: "treasurer".to_string(),
+        };
+        let outsider = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), outsider, withdraw(2));
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+
+        let admin = mock_info("creator", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), admin.clone(), withdraw(3)).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), admin.clone(), withdraw(0)).is_err());
+        let res = execute(deps.as_mut(), mock_env(), admin.clone(), withdraw(2)).unwrap();
+        assert_eq!(res.messages, vec![bank_send("treasurer", 2)]);
+        assert!(execute(deps.as_mut(), mock_env(), admin, withdraw(1)).is_err());
+    }
+
+    #[test]
+    fn update_config_test() {
+        let mut deps = init();
+        let msg = HandleMsg::UpdateConfig {
+            admin: Some("new_admin".to_string()),
+            house_fee_bps: None,
+            accepted_denoms: Some(vec!["uosmo".to_string()]),
+            accepted_cw20: None,
+        };
+        let outsider = mock_info("anyone", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), outsider, msg.clone()).is_err());
+        execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let too_high = HandleMsg::UpdateConfig {
+            admin: None,
+            house_fee_bps: Some(MAX_HOUSE_FEE_BPS + 1),
+            accepted_denoms: None,
+            accepted_cw20: None,
+        };
+        let admin = mock_info("new_admin", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), admin.clone(), too_high).is_err());
+        let no_denoms = HandleMsg::UpdateConfig {
+            admin: None,
+            house_fee_bps: None,
+            accepted_denoms: Some(vec![]),
+            accepted_cw20: Some(vec![]),
+        };
+        assert!(execute(deps.as_mut(), mock_env(), admin, no_denoms).is_err());
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(config.admin, Addr::unchecked("new_admin"));
+        assert_eq!(config.accepted_denoms, vec!["uosmo".to_string()]);
+        assert_eq!(config.accepted_cw20, vec![Addr::unchecked("token")]);
+    }
+
+    #[test]
+    fn more_than_256_rooms_test() {
+        let mut deps = init();
+        for i in 0..300u64 {
+            let res = add_test_room(&mut deps, Variant::Classic, None, 0, false, None).unwrap();
+            assert_eq!(from_binary::<u64>(&res.data.unwrap()).unwrap(), i);
+        }
+        accept_room(&mut deps, "player2", 299, 0);
+
+        let msg = HandleMsg::PlayMove { room_number: 299, action: Move::Place { coordinates: Coordinates::new(1, 1) } };
+        execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).unwrap();
+        assert!(Room::load_room(299, &deps.storage).unwrap().unwrap().board.cells[4].player.is_some());
+        assert!(Room::load_room(43, &deps.storage).unwrap().unwrap().board.cells[4].player.is_none());
+    }
+
+    #[test]
+    fn instantiate_sets_version_test() {
+        let deps = init();
+        let version = cw2::get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, CONTRACT_VERSION);
+    }
+
+    #[test]
+    fn migrate_legacy_layout_test() {
+        use crate::legacy::{LegacyRoom, LegacyState, LEGACY_CONFIG_KEY};
+        use cosmwasm_std::{to_vec, Storage};
+
+        let mut deps = mock_dependencies();
+        let legacy_state = LegacyState {
+            room_count: 2,
+            balance: Uint128::new(15),
+        };
+        deps.storage.set(LEGACY_CONFIG_KEY, &to_vec(&legacy_state).unwrap());
+        for room_number in 0..2u8 {
+            let mut board = Board::default();
+            board.occupy_cell(Addr::unchecked("player1"), Coordinates::new(1, 1), "X".to_string());
+            let legacy_room = LegacyRoom {
+                player1: Addr::unchecked("player1"),
+                player2: Addr::unchecked("player2"),
+                turn: Addr::unchecked("player2"),
+                board,
+                game_state: "InProgess".to_string(),
+                no_moves: 1,
+                total_coins_raised: Uint128::new(10),
+            };
+            deps.storage.set(&[room_number], &to_vec(&legacy_room).unwrap());
+        }
+
+        let no_admin = MigrateMsg { admin: None };
+        assert!(migrate(deps.as_mut(), mock_env(), no_admin).is_err());
+
+        let msg = MigrateMsg { admin: Some("admin".to_string()) };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+        assert!(deps.storage.get(&[0]).is_none());
+        let state = State::load_state(&deps.storage).unwrap();
+        assert_eq!(state.room_count, 2);
+        assert_eq!(state.admin, Addr::unchecked("admin"));
+        assert_eq!(state.treasury[0].amount, Uint128::new(15));
+
+        let room = Room::load_room(1, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.turn, Addr::unchecked("player2"));
+        assert_eq!(room.total_coins_raised, Uint128::new(10));
+        assert_eq!(cw2::get_contract_version(&deps.storage).unwrap().version, CONTRACT_VERSION);
+
+        // The migrated room can be finished and the escrowed coins paid out.
+        let results = play_moves(&mut deps, &[Coordinates::new(0, 0)]);
+        assert!(results[0].is_err());
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates: Coordinates::new(0, 0) } };
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn migrate_finished_legacy_rooms_test() {
+        use crate::legacy::{LegacyRoom, LegacyState, LEGACY_CONFIG_KEY};
+        use cosmwasm_std::{to_vec, Storage};
+
+        let mut deps = mock_dependencies();
+        let legacy_state = LegacyState {
+            room_count: 2,
+            balance: Uint128::new(15),
+        };
+        deps.storage.set(LEGACY_CONFIG_KEY, &to_vec(&legacy_state).unwrap());
+        let game_states = ["GameWon { player: Addr(\"player1\") }", "Tie"];
+        for (room_number, game_state) in game_states.iter().enumerate() {
+            let legacy_room = LegacyRoom {
+                player1: Addr::unchecked("player1"),
+                player2: Addr::unchecked("player2"),
+                turn: Addr::unchecked("player2"),
+                board: Board::default(),
+                game_state: game_state.to_string(),
+                no_moves: 9,
+                total_coins_raised: Uint128::new(30),
+            };
+            deps.storage.set(&[room_number as u8], &to_vec(&legacy_room).unwrap());
+        }
+
+        let msg = MigrateMsg { admin: Some("admin".to_string()) };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // The won pot was paid out already, the tied one joins the balance in the treasury.
+        for room_number in 0..2 {
+            let room = Room::load_room(room_number, &deps.storage).unwrap().unwrap();
+            assert!(room.is_finished());
+            assert_eq!(room.total_coins_raised, Uint128::zero());
+        }
+        let state = State::load_state(&deps.storage).unwrap();
+        assert_eq!(state.treasury[0].amount, Uint128::new(45));
+    }
+
+    #[test]
+    fn migrate_refuses_downgrade_test() {
+        let mut deps = init();
+        let msg = MigrateMsg { admin: None };
+        migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        assert!(migrate(deps.as_mut(), mock_env(), msg.clone()).is_err());
+
+        set_contract_version(&mut deps.storage, "crates.io:other", "0.1.0").unwrap();
+        assert!(migrate(deps.as_mut(), mock_env(), msg).is_err());
+    }
+
+    fn rooms_by_player(
+        deps: &OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        player: &str,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        status_filter: Option<RoomStatus>,
+    ) -> Vec<u64> {
+        let msg = QueryMsg::RoomsByPlayer {
+            player: player.to_string(),
+            start_after,
+            limit,
+            status_filter,
+        };
+        let res: RoomsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        res.rooms.iter().map(|info| info.room_id).collect()
     }
 
     #[test]
-    fn play_move_test() {
+    fn rooms_by_player_test() {
+        // Room 0 is staked and waiting for deposits, the rest are free and running.
+        let mut deps = init();
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("alice"),
+            player2: Addr::unchecked("bob"),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("alice", &[]), msg).unwrap();
+        add_free_room(&mut deps, "bob", "carol");
+        add_free_room(&mut deps, "carol", "alice");
+        add_free_room(&mut deps, "alice", "erin");
+        add_free_room(&mut deps, "bob", "alice");
+
+        assert_eq!(rooms_by_player(&deps, "alice", None, None, None), vec![0, 2, 3, 4]);
+        assert_eq!(rooms_by_player(&deps, "carol", None, None, None), vec![1, 2]);
+        assert_eq!(rooms_by_player(&deps, "alice", Some(0), Some(2), None), vec![2, 3]);
+        assert_eq!(rooms_by_player(&deps, "alice", Some(3), Some(2), None), vec![4]);
+        assert_eq!(rooms_by_player(&deps, "alice", None, None, Some(RoomStatus::Open)), vec![0]);
+        assert_eq!(
+            rooms_by_player(&deps, "bob", None, None, Some(RoomStatus::InProgress)),
+            vec![1, 4]
+        );
+        assert!(rooms_by_player(&deps, "dave", None, None, None).is_empty());
+
+        // Rooms migrated from before seats had to differ are listed once.
+        let mut room = Room::load_room(4, &deps.storage).unwrap().unwrap();
+        room.player1 = Addr::unchecked("alice");
+        Room::add_room(&mut deps.storage, &room).unwrap();
+        assert_eq!(rooms_by_player(&deps, "alice", Some(3), None, None), vec![4, 5]);
+        assert_eq!(rooms_by_player(&deps, "alice", Some(3), Some(1), None), vec![4]);
+
+        // A full page only hands back a cursor when more rooms follow.
+        let next = |limit| {
+            let msg = QueryMsg::RoomsByPlayer {
+                player: "alice".to_string(),
+                start_after: Some(3),
+                limit: Some(limit),
+                status_filter: None,
+            };
+            from_binary::<RoomsResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().next
+        };
+        assert_eq!(next(1), Some(4));
+        assert_eq!(next(2), None);
+    }
+
+    fn list_rooms(
+        deps: &OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        status_filter: Option<RoomStatus>,
+    ) -> Vec<u64> {
+        let msg = QueryMsg::RoomList {
+            start_after,
+            limit,
+            status_filter,
+        };
+        let res: RoomsResponse = from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        res.rooms.iter().map(|info| info.room_id).collect()
+    }
+
+    #[test]
+    fn room_list_test() {
         let mut deps = add_room_init();
+        for _ in 0..299 {
+            add_free_room(&mut deps, "alice", "bob");
+        }
+        play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+
+        assert_eq!(list_rooms(&deps, None, None, None), (0..10).collect::<Vec<u64>>());
+        assert_eq!(list_rooms(&deps, Some(9), Some(3), None), vec![10, 11, 12]);
+        assert_eq!(list_rooms(&deps, Some(250), Some(1000), None).len(), MAX_LIMIT as usize);
+        assert_eq!(list_rooms(&deps, Some(297), None, None), vec![298, 299]);
+        assert!(list_rooms(&deps, Some(299), None, None).is_empty());
+        assert_eq!(list_rooms(&deps, None, None, Some(RoomStatus::Won)), vec![0]);
+        assert!(list_rooms(&deps, None, None, Some(RoomStatus::Tie)).is_empty());
+        assert_eq!(list_rooms(&deps, None, Some(2), Some(RoomStatus::InProgress)), vec![1, 2]);
+
+        let next = |start_after, limit, status_filter| {
+            let msg = QueryMsg::RoomList { start_after, limit, status_filter };
+            from_binary::<RoomsResponse>(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap().next
+        };
+        // A full page only hands back a cursor when more rooms follow.
+        assert_eq!(next(Some(296), Some(2), None), Some(298));
+        assert_eq!(next(Some(297), Some(2), None), None);
+
+        // A filter nothing matches stops after MAX_SCAN rooms.
+        let tie = Some(RoomStatus::Tie);
+        assert_eq!(next(None, None, tie.clone()), Some(MAX_SCAN as u64 - 1));
+        assert_eq!(next(Some(99), None, tie.clone()), Some(199));
+        // The last hundred rooms fill the scan exactly, nothing is left after them.
+        assert_eq!(next(Some(199), None, tie.clone()), None);
+        assert_eq!(next(Some(299), None, tie), None);
+    }
 
-        let coordinates = Coordinates::new(1, 1);
+    #[test]
+    fn migrate_rebuilds_player_indexes_test() {
+        use crate::legacy::UntypedStateRoom;
 
-        let msg = HandleMsg::PlayMove { coordinates, room_number: 0 };
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
+        let mut deps = init();
+        let mut board = Board::default();
+        for y in 0..3 {
+            board.occupy_cell(Addr::unchecked("alice"), Coordinates::new(0, y), "X".to_string());
+        }
+        let untyped = UntypedStateRoom {
+            player1: Addr::unchecked("alice"),
+            player2: Addr::unchecked("bob"),
+            turn: Addr::unchecked("bob"),
+            board,
+            game_state: "GameWon { player: Addr(\"alice\") }".to_string(),
+            no_moves: 5,
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::zero(),
+            player1_deposited: false,
+            player2_deposited: false,
+            total_coins_raised: Uint128::new(20),
+        };
+        let unindexed: cw_storage_plus::Map<u64, UntypedStateRoom> = cw_storage_plus::Map::new("rooms");
+        unindexed.save(&mut deps.storage, 0, &untyped).unwrap();
+        let mut state = State::load_state(&deps.storage).unwrap();
+        state.room_count = 1;
+        state.save_state(&mut deps.storage).unwrap();
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.2.0").unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap();
+        assert_eq!(rooms_by_player(&deps, "bob", None, None, None), vec![0]);
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        match room.game_state {
+            GameState::Won { player, line } => {
+                assert_eq!(player, Addr::unchecked("alice"));
+                assert_eq!(line.len(), 3);
+            }
+            state => panic!("unexpected game state {:?}", state),
+        }
+        // The pot was paid out when the game was won.
+        assert_eq!(room.total_coins_raised, Uint128::zero());
+    }
 
-        assert!(play_move.is_ok());
+    #[test]
+    fn won_state_test() {
+        let mut deps = add_room_init();
+        play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(
+            room.game_state,
+            GameState::Won {
+                player: Addr::unchecked("player1"),
+                line: vec![
+                    Coordinates::new(0, 0),
+                    Coordinates::new(0, 1),
+                    Coordinates::new(0, 2),
+                ],
+            }
+        );
     }
 
     #[test]
-    fn play_same_move_test() {
+    fn resign_test() {
         let mut deps = add_room_init();
+        let resign = HandleMsg::Resign { room_number: 0 };
+        let outsider = mock_info("anyone", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), outsider, resign.clone()).is_err());
+
+        let player1_info = mock_info("player1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), player1_info, resign.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player2", 20)]);
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(
+            room.game_state,
+            GameState::Resigned {
+                player: Addr::unchecked("player1")
+            }
+        );
+        assert_eq!(room.total_coins_raised, Uint128::zero());
+        let player2_info = mock_info("player2", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), player2_info, resign).is_err());
+        assert!(play_moves(&mut deps, &[Coordinates::new(0, 0)])[0].is_err());
+    }
 
-        let coordinates = Coordinates::new(1, 1);
+    #[test]
+    fn gomoku_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Classic, Some(BoardConfig { width: 15, height: 15, k: 5, depth: 1 }), 0, false, None).unwrap();
+
+        // Player1 builds a diagonal, four in a row is not enough.
+        let mut all_coordinates = vec![];
+        for i in 0..5 {
+            all_coordinates.push(Coordinates::new(10 - i, 3 + i));
+            all_coordinates.push(Coordinates::new(14, i));
+        }
+        all_coordinates.pop();
+        let results = play_moves(&mut deps, &all_coordinates);
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..5).rev().map(|i| Coordinates::new(10 - i, 3 + i)).collect::<Vec<_>>()
+        );
+        assert_eq!(status.no_moves, 9);
+    }
 
-        let msg = HandleMsg::PlayMove { coordinates, room_number: 0 };
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let player2_info = mock_info("player2", &coins(10, "ioc"));
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg.clone());
-        assert!(play_move.is_ok());
+    #[test]
+    fn full_board_tie_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Classic, Some(BoardConfig { width: 4, height: 3, k: 4, depth: 1 }), 0, false, None).unwrap();
+
+        let mut all_coordinates = vec![];
+        for x in 0..3 {
+            for y in 0..4 {
+                all_coordinates.push(Coordinates::new(x, y));
+            }
+        }
+        let results = play_moves(&mut deps, &all_coordinates);
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(game_status(&deps).game_state, GameState::Tie);
+    }
 
-        let play_move = execute(deps.as_mut(), mock_env(), player2_info, msg);
-        assert!(play_move.is_err())
+    #[test]
+    fn invalid_board_test() {
+        let mut deps = init();
+        let invalid = [
+            BoardConfig { width: 2, height: 3, k: 3, depth: 1 },
+            BoardConfig { width: 20, height: 3, k: 3, depth: 1 },
+            BoardConfig { width: 5, height: 4, k: 6, depth: 1 },
+            BoardConfig { width: 5, height: 4, k: 2, depth: 1 },
+        ];
+        for board in invalid {
+            assert!(start_test_room(&mut deps, Variant::Classic, Some(board), 0, false, None).is_err());
+        }
+
+        start_test_room(&mut deps, Variant::Classic, Some(BoardConfig::default()), 0, false, None).unwrap();
+        let results = play_moves(&mut deps, &[Coordinates::new(3, 0)]);
+        assert!(results[0].is_err());
     }
 
     #[test]
-    fn play_same_player_test() {
-        let mut deps = add_room_init();
+    fn free_room_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Classic, None, 0, false, None).unwrap();
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert!(results[4].as_ref().unwrap().messages.is_empty());
+    }
 
-        let coordinates = Coordinates::new(1, 1);
-        let msg = HandleMsg::PlayMove {
-            coordinates,
-            room_number: 0
-        };
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
-        assert!(play_move.is_ok());
+    fn drops(columns: &[u8]) -> Vec<Move> {
+        columns.iter().map(|column| Move::Drop { column: *column }).collect()
+    }
 
-        let coordinates = Coordinates::new(1, 2);
-        let msg = HandleMsg::PlayMove {
-            coordinates,
-            room_number: 0
-        };
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info, msg);
-        assert!(play_move.is_err());
+    #[test]
+    fn connect_four_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::ConnectFour, None, 0, false, None).unwrap();
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!((room.board.width, room.board.height, room.board.k), (7, 6, 4));
+
+        // Player1 stacks column 3 while player2 answers in column 4.
+        let results = play_actions(&mut deps, &drops(&[3, 4, 3, 4, 3, 4]));
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(game_status(&deps).game_state, GameState::InProgress);
+
+        let results = play_actions(&mut deps, &drops(&[3]));
+        assert!(results[0].is_ok());
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..4).map(|x| Coordinates::new(x, 3)).collect::<Vec<_>>()
+        );
     }
+
     #[test]
-    fn restart_game() {
-        let mut deps = add_room_init();
+    fn connect_four_moves_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::ConnectFour, None, 0, false, None).unwrap();
+
+        // Only drops are accepted, and only into columns that still have room.
+        let results = play_moves(&mut deps, &[Coordinates::new(0, 0)]);
+        assert!(results[0].is_err());
+        let results = play_actions(&mut deps, &drops(&[7]));
+        assert!(results[0].is_err());
+        let results = play_actions(&mut deps, &drops(&[0, 0, 0, 0, 0, 0, 0]));
+        assert!(results[..6].iter().all(|res| res.is_ok()));
+        assert!(results[6].is_err());
+
+        add_free_room(&mut deps, "player1", "player2");
+        let msg = HandleMsg::PlayMove { room_number: 1, action: Move::Drop { column: 0 } };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
+    }
 
+    #[test]
+    fn connect_four_board_status_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::ConnectFour, None, 0, false, None).unwrap();
+        play_actions(&mut deps, &drops(&[0, 0, 6]));
+
+        let msg = QueryMsg::BoardStatus { room_number: 0 };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let status = from_binary::<TableStatusResponse>(&res).unwrap().status;
+        let rows = status.lines().filter(|line| line.starts_with("||")).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[5], "|| X |   |   |   |   |   | X ||");
+        assert_eq!(rows[4], "|| O |   |   |   |   |   |   ||");
+        assert_eq!(rows[0], "||   |   |   |   |   |   |   ||");
+    }
 
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let player2_info = mock_info("player2", &coins(10, "ioc"));
-        let coordinates = Coordinates::new(1, 1);
-        let msg = HandleMsg::PlayMove {
-            coordinates,
-            room_number: 0
-        };
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
-        assert!(play_move.is_ok());
-        let coordinates = Coordinates::new(2, 1);
-        let msg = HandleMsg::PlayMove {
-            coordinates,
-            room_number: 0
-        };
-        let play_move = execute(deps.as_mut(), mock_env(), player2_info.clone(), msg.clone());
-        assert!(play_move.is_ok());
+    fn on_board(moves: &[(u8, u8, u8)]) -> Vec<Move> {
+        moves
+            .iter()
+            .map(|(board, x, y)| Move::PlaceOnBoard { board: *board, coordinates: Coordinates::new(*x, *y) })
+            .collect()
+    }
 
-        let restart = HandleMsg::RestartGame { room_number: 0};
-        let restart_game = execute(deps.as_mut(), mock_env(), player1_info.clone(), restart);
+    #[test]
+    fn ultimate_forced_board_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Ultimate, None, 0, false, None).unwrap();
+
+        let results = play_actions(&mut deps, &on_board(&[(4, 0, 2)]));
+        assert!(results[0].is_ok());
+        assert_eq!(game_status(&deps).next_board, Some(2));
+
+        // Player2 is sent to board 2 by the cell player1 picked.
+        let msg = HandleMsg::PlayMove { room_number: 0, action: on_board(&[(3, 1, 1)])[0].clone() };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates: Coordinates::new(1, 1) } };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
+        let msg = HandleMsg::PlayMove { room_number: 0, action: on_board(&[(2, 1, 1)])[0].clone() };
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).unwrap();
+        assert_eq!(game_status(&deps).next_board, Some(4));
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.boards.len(), 9);
+        assert!(room.boards[4].cells[2].player.is_some());
+        assert!(room.boards[2].cells[4].player.is_some());
+        assert!(room.board.cells.iter().all(|cell| cell.player.is_none()));
+    }
 
-        assert!(restart_game.is_ok());
+    #[test]
+    fn ultimate_win_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Ultimate, None, 0, false, None).unwrap();
+
+        // Player1 takes the top row of sub-boards while player2 keeps sending them back there.
+        let moves = on_board(&[
+            (0, 1, 0), (3, 0, 0), (0, 1, 1), (4, 0, 0), (0, 1, 2), (5, 0, 0),
+            (1, 0, 0), (3, 0, 1), (1, 1, 1), (4, 0, 1), (1, 2, 2), (8, 0, 0),
+            (2, 0, 0), (5, 0, 1), (2, 0, 1), (6, 0, 0), (2, 0, 2),
+        ]);
+        let results = play_actions(&mut deps, &moves);
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.board.cells[0].player, Some(Addr::unchecked("player1")));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|y| Coordinates::new(0, y)).collect::<Vec<_>>()
+        );
+        assert_eq!(status.no_moves, 17);
+        assert_eq!(status.next_board, None);
+
+        // The meta-board line ends the game.
+        let msg = HandleMsg::PlayMove { room_number: 0, action: on_board(&[(7, 1, 1)])[0].clone() };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
     }
+
     #[test]
-    fn play_game_in_not_existing_room_test() {
-        let mut deps = add_room_init();
+    fn ultimate_board_size_test() {
+        let mut deps = init();
+        let board = BoardConfig { width: 4, height: 4, k: 3, depth: 1 };
+        assert!(add_test_room(&mut deps, Variant::Ultimate, Some(board), 0, false, None).is_err());
+    }
 
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
+    #[test]
+    fn board_lines_test() {
+        assert_eq!(Board::default().lines().len(), 8);
+        let cube = BoardConfig { width: 4, height: 4, k: 4, depth: 4 };
+        assert_eq!(Board::new(&cube).lines().len(), 76);
+        let connect_four = Variant::ConnectFour.default_board();
+        assert_eq!(Board::new(&connect_four).lines().len(), 69);
+    }
 
-        let coordinates = Coordinates::new(2, 0);
-        let msg = HandleMsg::PlayMove {
-            coordinates,
-            room_number: 1,
-        };
-        let play_move = execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
-        assert!(play_move.is_err())
+    #[test]
+    fn qubic_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Qubic, None, 0, false, None).unwrap();
+
+        // Moves on a cube need a layer.
+        let results = play_moves(&mut deps, &[Coordinates::new(0, 0)]);
+        assert!(results[0].is_err());
+
+        // Player1 takes the long diagonal through the cube, player2 stays on the bottom row of layer 0.
+        let mut all_coordinates = vec![];
+        for i in 0..4 {
+            all_coordinates.push(Coordinates::new_3d(i, i, i));
+            all_coordinates.push(Coordinates::new_3d(3, i, 0));
+        }
+        all_coordinates.pop();
+        let results = play_moves(&mut deps, &all_coordinates);
+        assert!(results.iter().all(|res| res.is_ok()));
+
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..4).map(|i| Coordinates::new_3d(i, i, i)).collect::<Vec<_>>()
+        );
+
+        let msg = QueryMsg::BoardStatus { room_number: 0 };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let status = from_binary::<TableStatusResponse>(&res).unwrap().status;
+        assert!(status.starts_with("Layer 0\n"));
+        assert!(status.contains("Layer 3\n"));
+        assert_eq!(status.lines().filter(|line| line.starts_with("||")).count(), 16);
     }
 
     #[test]
-    fn play_winning_game_test() {
-        let mut deps = add_room_init();
+    fn layered_board_variant_test() {
+        let mut deps = init();
+        let cube = BoardConfig { width: 4, height: 4, k: 4, depth: 4 };
+        assert!(start_test_room(&mut deps, Variant::Classic, Some(cube), 0, false, None).is_err());
+        let too_deep = BoardConfig { width: 4, height: 4, k: 4, depth: 9 };
+        assert!(start_test_room(&mut deps, Variant::Classic, Some(too_deep), 0, false, None).is_err());
+    }
 
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let player2_info = mock_info("player2", &coins(10, "ioc"));
-        let all_coordinates: Vec<Coordinates> = vec![
-            Coordinates::new(0, 0),
-            Coordinates::new(0, 1),
-            Coordinates::new(1, 0),
-            Coordinates::new(1, 1),
-            Coordinates::new(2, 0),
-            Coordinates::new(2, 1),
+    #[test]
+    fn misere_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Classic, None, 10, true, None).unwrap();
+
+        // Player1 completes the top row and loses.
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert_eq!(results[4].as_ref().unwrap().messages, vec![bank_send("player2", 20)]);
+
+        let status = game_status(&deps);
+        assert!(status.misere);
+        assert_eq!(status.winner, Some(Addr::unchecked("player2")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|y| Coordinates::new(0, y)).collect::<Vec<_>>()
+        );
+        let msg = QueryMsg::RoomList { start_after: None, limit: None, status_filter: None };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        assert!(from_binary::<RoomsResponse>(&res).unwrap().rooms[0].room.misere);
+    }
+
+    fn place_sign(x: u8, y: u8, sign: &str) -> Move {
+        Move::PlaceSign { coordinates: Coordinates::new(x, y), sign: sign.to_string() }
+    }
+
+    #[test]
+    fn wild_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Wild, None, 0, false, None).unwrap();
+
+        assert!(play_moves(&mut deps, &[Coordinates::new(0, 0)])[0].is_err());
+        assert!(play_actions(&mut deps, &[place_sign(0, 0, "Z")])[0].is_err());
+
+        // Player2 finishes the row of X player1 started.
+        let results = play_actions(
+            &mut deps,
+            &[place_sign(0, 0, "X"), place_sign(0, 1, "X"), place_sign(1, 1, "O"), place_sign(0, 2, "X")],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player2")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|y| Coordinates::new(0, y)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn misere_variants_test() {
+        let mut deps = init();
+        let variants = [
+            Variant::Notakto { boards: 2 },
+            Variant::OrderAndChaos { order: Addr::unchecked("player1") },
+            Variant::Quantum,
         ];
+        for variant in variants {
+            assert!(add_test_room(&mut deps, variant, None, 0, true, None).is_err());
+        }
+    }
 
-        for i in 0..all_coordinates.len() {
-            let msg = HandleMsg::PlayMove {
-                coordinates: all_coordinates[i].clone(),
-                room_number: 0
-            };
-            if i % 2 == 0 {
-                let play_move =
-                    execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
-                
-                    assert!(play_move.is_ok());
-            } else {
-                let play_move =
-                    execute(deps.as_mut(), mock_env(), player2_info.clone(), msg.clone());
-                if i != 5 {
-                    assert!(play_move.is_ok())
-                } else {
-                    assert!(play_move.is_err());
-                }
-            }
+    #[test]
+    fn notakto_test() {
+        let mut deps = init();
+        assert!(start_test_room(&mut deps, Variant::Notakto { boards: 0 }, None, 0, false, None).is_err());
+        assert!(start_test_room(&mut deps, Variant::Notakto { boards: 10 }, None, 0, false, None).is_err());
+        start_test_room(&mut deps, Variant::Notakto { boards: 2 }, None, 0, false, None).unwrap();
+
+        // Player1 kills board 0, the game goes on on board 1.
+        let results = play_actions(&mut deps, &on_board(&[(0, 0, 0), (0, 0, 1), (0, 0, 2)]));
+        assert!(results.iter().all(|res| res.is_ok()));
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.dead_boards, vec![0]);
+        assert_eq!(room.boards[0].cells[1].sign, "X");
+        assert_eq!(room.game_state, GameState::InProgress);
+
+        let player2_info = mock_info("player2", &[]);
+        let msg = HandleMsg::PlayMove { room_number: 0, action: on_board(&[(0, 2, 2)])[0].clone() };
+        assert!(execute(deps.as_mut(), mock_env(), player2_info.clone(), msg).is_err());
+        let msg = HandleMsg::PlayMove { room_number: 0, action: Move::Place { coordinates: Coordinates::new(2, 2) } };
+        assert!(execute(deps.as_mut(), mock_env(), player2_info.clone(), msg).is_err());
+
+        // Player2 kills the last board and loses.
+        for (player, (x, y)) in [("player2", (0, 0)), ("player1", (1, 1)), ("player2", (2, 2))] {
+            let msg = HandleMsg::PlayMove { room_number: 0, action: on_board(&[(1, x, y)])[0].clone() };
+            execute(deps.as_mut(), mock_env(), mock_info(player, &[]), msg).unwrap();
         }
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|i| Coordinates::new(i, i)).collect::<Vec<_>>()
+        );
+
+        let msg = QueryMsg::BoardStatus { room_number: 0 };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let status = from_binary::<TableStatusResponse>(&res).unwrap().status;
+        assert!(status.starts_with("Board 0 (dead)\n"));
+        assert!(status.contains("Board 1 (dead)\n"));
+    }
+
+    fn slide(from: (u8, u8), to: (u8, u8)) -> Move {
+        Move::Slide { from: Coordinates::new(from.0, from.1), to: Coordinates::new(to.0, to.1) }
+    }
+
+    fn add_morris_room(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
+        start_test_room(deps, Variant::ThreeMensMorris, None, 0, false, None).unwrap();
+        // Player1 holds (0, 0), (0, 2) and (2, 1), player2 (1, 0), (0, 1) and (2, 0).
+        let results = play_moves(
+            deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 0),
+                Coordinates::new(0, 2),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 1),
+                Coordinates::new(2, 0),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
     }
 
     #[test]
-    fn play_tie_game_test() {
-        let mut deps = add_room_init();
-        let player1_info = mock_info("player1", &coins(10, "ioc"));
-        let player2_info = mock_info("player2", &coins(10, "ioc"));
+    fn morris_slide_before_placing_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::ThreeMensMorris, None, 0, false, None).unwrap();
+        assert!(play_actions(&mut deps, &[slide((0, 0), (0, 1))])[0].is_err());
+    }
 
-        let all_coordinates: Vec<Coordinates> = vec![
-            Coordinates::new(0, 0),
-            Coordinates::new(0, 1),
-            Coordinates::new(0, 2),
-            Coordinates::new(2, 0),
-            Coordinates::new(2, 1),
-            Coordinates::new(2, 2),
-            Coordinates::new(1, 0),
-            Coordinates::new(1, 1),
-            Coordinates::new(1, 2),
+    #[test]
+    fn morris_win_test() {
+        let mut deps = init();
+        add_morris_room(&mut deps);
+
+        let invalid = [
+            Move::Place { coordinates: Coordinates::new(1, 1) },
+            slide((1, 0), (1, 1)),
+            slide((0, 0), (2, 2)),
+            slide((2, 1), (1, 2)),
+            slide((0, 0), (0, 1)),
         ];
+        for action in invalid {
+            assert!(play_actions(&mut deps, &[action])[0].is_err());
+        }
 
-        for i in 0..all_coordinates.len() {
-            let msg = HandleMsg::PlayMove {
-                coordinates: all_coordinates[i].clone(),
-                room_number: 0
-            };
-            if i % 2 == 0 {
-                let play_move =
-                    execute(deps.as_mut(), mock_env(), player1_info.clone(), msg.clone());
-                assert!(play_move.is_ok());
-            } else {
-                let play_move =
-                    execute(deps.as_mut(), mock_env(), player2_info.clone(), msg.clone());
-                assert!(play_move.is_ok())
+        let results = play_actions(&mut deps, &[slide((2, 1), (2, 2)), slide((2, 0), (2, 1)), slide((0, 2), (1, 1))]);
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|i| Coordinates::new(i, i)).collect::<Vec<_>>()
+        );
+        assert_eq!(status.no_moves, 9);
+    }
+
+    #[test]
+    fn morris_repetition_test() {
+        let mut deps = init();
+        add_morris_room(&mut deps);
+
+        let shuffle = [slide((2, 1), (2, 2)), slide((1, 0), (1, 1)), slide((2, 2), (2, 1)), slide((1, 1), (1, 0))];
+        let actions = shuffle.iter().cycle().take(9).cloned().collect::<Vec<_>>();
+        let results = play_actions(&mut deps, &actions);
+        assert!(results.iter().all(|res| res.is_ok()));
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.positions.len(), 9);
+        assert_eq!(room.game_state, GameState::Tie);
+    }
+
+    #[test]
+    fn morris_blocked_test() {
+        let (alice, bob) = (Addr::unchecked("alice"), Addr::unchecked("bob"));
+        // Each bit of a mask is a piece on the cell of that index.
+        let morris_board = |alice_cells: u16, bob_cells: u16| {
+            let mut board = Board::default();
+            for (i, coordinates) in Board::default().cells.iter().map(|cell| cell.coordinates.clone()).enumerate() {
+                if alice_cells & (1 << i) != 0 {
+                    board.occupy_cell(alice.clone(), coordinates, "X".to_string());
+                } else if bob_cells & (1 << i) != 0 {
+                    board.occupy_cell(bob.clone(), coordinates, "O".to_string());
+                }
+            }
+            board
+        };
+        let has_line = |board: &Board, player: &Addr| {
+            board.lines().iter().any(|line| {
+                line.iter()
+                    .all(|coordinates| board.cells[board.index(coordinates).unwrap()].player.as_ref() == Some(player))
+            })
+        };
+
+        // Alice's top row walled in by Bob's middle one can't slide.
+        assert!(!morris_board(0b000_000_111, 0b000_111_000).can_slide(&alice));
+
+        // Short of a line, three pieces each always leave both players a slide on the 3x3 board.
+        let threes = (0..1u16 << 9).filter(|cells| cells.count_ones() == 3).collect::<Vec<_>>();
+        for alice_cells in &threes {
+            for bob_cells in threes.iter().filter(|bob_cells| *bob_cells & alice_cells == 0) {
+                let board = morris_board(*alice_cells, *bob_cells);
+                if !has_line(&board, &alice) && !has_line(&board, &bob) {
+                    assert!(board.can_slide(&alice) && board.can_slide(&bob));
+                }
             }
         }
-        let msg = HandleMsg::PlayMove {
-            coordinates: all_coordinates[0].clone(),
-            room_number: 0
+    }
+
+    fn place_number(x: u8, y: u8, number: u8) -> Move {
+        Move::PlaceNumber { coordinates: Coordinates::new(x, y), number }
+    }
+
+    #[test]
+    fn numerical_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Numerical, None, 0, false, None).unwrap();
+
+        let results = play_actions(&mut deps, &[place_number(1, 1, 5), place_number(0, 0, 2)]);
+        assert!(results.iter().all(|res| res.is_ok()));
+        for action in [place_number(0, 1, 4), place_number(0, 1, 5), place_number(0, 1, 11), place_number(0, 0, 3)] {
+            assert!(play_actions(&mut deps, &[action])[0].is_err());
+        }
+
+        // Player2 completes the diagonal 2 + 5 + 8, whoever placed the other numbers.
+        let results = play_actions(&mut deps, &[place_number(0, 1, 1), place_number(2, 2, 8)]);
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player2")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|i| Coordinates::new(i, i)).collect::<Vec<_>>()
+        );
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.player1_numbers, vec![5, 1]);
+        assert_eq!(room.player2_numbers, vec![2, 8]);
+        assert_eq!(room.board.cells[8].value, Some(8));
+        assert_eq!(room.board.cells[8].sign, "8");
+    }
+
+    #[test]
+    fn order_wins_test() {
+        let mut deps = init();
+        let order = |player: &str| Variant::OrderAndChaos { order: Addr::unchecked(player) };
+        assert!(start_test_room(&mut deps, order("anyone"), None, 0, false, None).is_err());
+        start_test_room(&mut deps, order("player1"), None, 0, false, None).unwrap();
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!((room.board.width, room.board.height, room.board.k), (6, 6, 5));
+
+        // Chaos is forced to place the fifth X, which still counts for Order.
+        let results = play_actions(
+            &mut deps,
+            &[
+                place_sign(0, 0, "X"), place_sign(5, 5, "O"), place_sign(0, 1, "X"), place_sign(5, 4, "O"),
+                place_sign(0, 2, "X"), place_sign(5, 3, "O"), place_sign(0, 3, "X"), place_sign(0, 4, "X"),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..5).map(|y| Coordinates::new(0, y)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn order_and_chaos_overline_test() {
+        let mut deps = init();
+        let order = Variant::OrderAndChaos { order: Addr::unchecked("player1") };
+        start_test_room(&mut deps, order, None, 0, false, None).unwrap();
+
+        // The sixth X joins two runs into a row of six, which is not a win for Order.
+        let results = play_actions(
+            &mut deps,
+            &[
+                place_sign(0, 0, "X"), place_sign(0, 1, "X"), place_sign(0, 3, "X"),
+                place_sign(0, 4, "X"), place_sign(0, 5, "X"), place_sign(0, 2, "X"),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.game_state, GameState::InProgress);
+        assert_eq!(status.winner, None);
+    }
+
+    #[test]
+    fn chaos_wins_test() {
+        let mut deps = init();
+        let order = Variant::OrderAndChaos { order: Addr::unchecked("player2") };
+        start_test_room(&mut deps, order, Some(BoardConfig::default()), 0, false, None).unwrap();
+        assert_eq!(game_status(&deps).turn, Some(Addr::unchecked("player2")));
+
+        // The board fills up without three identical signs in a row.
+        let signs = ["X", "X", "O", "O", "O", "X", "X", "X", "O"];
+        for (i, sign) in signs.iter().enumerate() {
+            let player = if i % 2 == 0 { "player2" } else { "player1" };
+            let action = place_sign(i as u8 / 3, i as u8 % 3, sign);
+            let msg = HandleMsg::PlayMove { room_number: 0, action };
+            execute(deps.as_mut(), mock_env(), mock_info(player, &[]), msg).unwrap();
+        }
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(status.winning_line, Some(vec![]));
+    }
+
+    fn spooky(first: (u8, u8), second: (u8, u8)) -> Move {
+        Move::Spooky { first: Coordinates::new(first.0, first.1), second: Coordinates::new(second.0, second.1) }
+    }
+
+    #[test]
+    fn quantum_collapse_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Quantum, None, 0, false, None).unwrap();
+        assert!(play_actions(&mut deps, &[spooky((0, 0), (0, 0))])[0].is_err());
+
+        // X3 closes a cycle with X1, player2 has to collapse it before moving.
+        let results = play_actions(&mut deps, &[spooky((0, 0), (0, 1)), spooky((1, 0), (1, 1)), spooky((0, 1), (0, 0))]);
+        assert!(results.iter().all(|res| res.is_ok()));
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.pending_collapse, Some(3));
+        assert_eq!(room.board.cells[0].spooky.len(), 2);
+
+        let collapse = |x, y| HandleMsg::PlayMove { room_number: 0, action: Move::Collapse { coordinates: Coordinates::new(x, y) } };
+        let player2_move = HandleMsg::PlayMove { room_number: 0, action: spooky((2, 0), (2, 1)) };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), player2_move.clone()).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), collapse(0, 0)).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), collapse(1, 0)).is_err());
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), collapse(0, 0)).unwrap();
+
+        // X3 lands where it was collapsed and pushes X1 into its other cell.
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.pending_collapse, None);
+        assert_eq!(room.turn, Addr::unchecked("player2"));
+        assert_eq!((room.board.cells[0].sign.as_str(), room.board.cells[0].value), ("X", Some(3)));
+        assert_eq!((room.board.cells[1].sign.as_str(), room.board.cells[1].value), ("X", Some(1)));
+        assert!(room.board.cells[0].spooky.is_empty() && room.board.cells[1].spooky.is_empty());
+        assert_eq!(room.board.cells[3].spooky.len(), 1);
+        assert_eq!(room.game_state, GameState::InProgress);
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), collapse(1, 0)).is_err());
+        let player2_move = HandleMsg::PlayMove { room_number: 0, action: spooky((0, 0), (2, 1)) };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), player2_move).is_err());
+        let player2_move = HandleMsg::PlayMove { room_number: 0, action: spooky((2, 0), (2, 1)) };
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), player2_move).unwrap();
+    }
+
+    #[test]
+    fn quantum_win_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Quantum, None, 0, false, None).unwrap();
+
+        // Player1 entangles the whole top row, any collapse of the cycle gives them the line.
+        let results = play_actions(
+            &mut deps,
+            &[
+                spooky((0, 0), (0, 1)),
+                spooky((2, 0), (2, 1)),
+                spooky((0, 1), (0, 2)),
+                spooky((2, 1), (2, 2)),
+                spooky((0, 0), (0, 2)),
+                Move::Collapse { coordinates: Coordinates::new(0, 0) },
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        let status = game_status(&deps);
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(
+            status.winning_line.unwrap(),
+            (0..3).map(|y| Coordinates::new(0, y)).collect::<Vec<_>>()
+        );
+
+        let msg = QueryMsg::BoardStatus { room_number: 0 };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let status = from_binary::<TableStatusResponse>(&res).unwrap().status;
+        assert!(status.contains("(2, 1): O2 O4\n"));
+    }
+
+    #[test]
+    fn quantum_simultaneous_lines_test() {
+        let mut board = Board::default();
+        let classical = [
+            (0, "player1", "X", 1), (1, "player1", "X", 3), (2, "player1", "X", 7),
+            (6, "player2", "O", 2), (7, "player2", "O", 4), (8, "player2", "O", 6),
+        ];
+        for (cell_index, player, sign, subscript) in classical {
+            board.cells[cell_index].occupy(Addr::unchecked(player), sign.to_string());
+            board.cells[cell_index].value = Some(subscript);
+        }
+        // Player2's line was complete after move 6, player1's only after move 7.
+        let (winner, line) = board.classical_winner().unwrap();
+        assert_eq!(winner, Addr::unchecked("player2"));
+        assert_eq!(line, (0..3).map(|y| Coordinates::new(2, y)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn open_room_test() {
+        let mut deps = init();
+        let msg = HandleMsg::CreateRoom {
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
         };
-        let play_move = execute(deps.as_mut(), mock_env(), player2_info.clone(), msg.clone());
-        assert!(play_move.is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg);
+        assert!(res.is_err());
+        open_room(&mut deps, 10);
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.player2, None);
+        assert_eq!(room.game_state, GameState::WaitingForOpponent);
+        assert_eq!(room.total_coins_raised, Uint128::new(10));
+
+        let msg = HandleMsg::JoinRoom { room_id: 0 };
+        let own_room = mock_info("player1", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), own_room, msg.clone()).is_err());
+        let wrong_stake = mock_info("player2", &coins(5, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), wrong_stake, msg.clone()).is_err());
+        let no_stake = mock_info("player2", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), no_stake, msg.clone()).is_err());
+        let info = mock_info("player2", &coins(10, "ioc"));
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("anyone", &coins(10, "ioc")), msg).is_err());
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.player2, Some(Addr::unchecked("player2")));
+        assert_eq!(room.game_state, GameState::InProgress);
+        assert_eq!(rooms_by_player(&deps, "player2", None, None, None), vec![0]);
+
+        // A joined room can't be cancelled any more.
+        let msg = HandleMsg::CancelRoom { room_id: 0 };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).is_err());
+
+        let results = play_moves(
+            &mut deps,
+            &[
+                Coordinates::new(0, 0),
+                Coordinates::new(1, 1),
+                Coordinates::new(0, 1),
+                Coordinates::new(2, 2),
+                Coordinates::new(0, 2),
+            ],
+        );
+        assert!(results.iter().all(|res| res.is_ok()));
+        assert_eq!(results[4].as_ref().unwrap().messages, vec![bank_send("player1", 20)]);
+    }
+
+    #[test]
+    fn free_open_room_test() {
+        let mut deps = init();
+        open_room(&mut deps, 0);
+        let msg = HandleMsg::JoinRoom { room_id: 0 };
+        execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).unwrap();
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.game_state, GameState::InProgress);
+    }
+
+    #[test]
+    fn cancel_room_test() {
+        let mut deps = init();
+        open_room(&mut deps, 10);
+
+        let msg = HandleMsg::CancelRoom { room_id: 0 };
+        let outsider = mock_info("anyone", &[]);
+        let res = execute(deps.as_mut(), mock_env(), outsider, msg.clone());
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.game_state, GameState::Abandoned);
+        assert_eq!(room.total_coins_raised, Uint128::zero());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).is_err());
+
+        let msg = HandleMsg::JoinRoom { room_id: 0 };
+        let info = mock_info("player2", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), info, msg).is_err());
+    }
+
+    #[test]
+    fn withdraw_before_start_test() {
+        let mut deps = init();
+        add_test_room(&mut deps, Variant::Classic, None, 10, false, Some(60)).unwrap();
+        let info = mock_info("player1", &coins(10, "ioc"));
+        execute(deps.as_mut(), mock_env(), info, HandleMsg::Deposit { room_number: 0 }).unwrap();
+
+        // Player2 never deposits, player1 can still get their stake back.
+        let msg = HandleMsg::CancelRoom { room_id: 0 };
+        let outsider = mock_info("anyone", &[]);
+        assert!(execute(deps.as_mut(), mock_env(), outsider, msg.clone()).is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.game_state, GameState::Abandoned);
+        assert_eq!(room.total_coins_raised, Uint128::zero());
+
+        // A deposit made by player2 goes back to player2, whoever cancels.
+        add_test_room(&mut deps, Variant::Classic, None, 10, false, None).unwrap();
+        accept_room(&mut deps, "player2", 1, 10);
+        let msg = HandleMsg::CancelRoom { room_id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player2", 10)]);
+
+        // The challenger can withdraw a challenge nobody answered.
+        send_challenge(&mut deps, Expiration::Never {}).unwrap();
+        let msg = HandleMsg::CancelRoom { room_id: 2 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+
+        // A started room can't be cancelled.
+        let mut deps = add_room_init();
+        let msg = HandleMsg::CancelRoom { room_id: 0 };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).is_err());
+    }
+
+    #[test]
+    fn same_players_test() {
+        let mut deps = init();
+        let msg = HandleMsg::AddRoom {
+            player1: Addr::unchecked("player1"),
+            player2: Addr::unchecked("player1"),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &coins(10, "ioc")), msg);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn cw20_open_room_test() {
+        let mut deps = init();
+        let create_room = ReceiveMsg::CreateRoom {
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let msg = cw20_send("player1", 25, &create_room);
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+
+        let join_room = ReceiveMsg::JoinRoom { room_id: 0 };
+        let wrong_amount = cw20_send("player2", 20, &join_room);
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("token", &[]), wrong_amount).is_err());
+        let msg = cw20_send("player2", 25, &join_room);
+        execute(deps.as_mut(), mock_env(), mock_info("token", &[]), msg).unwrap();
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.denom, Denom::Cw20(Addr::unchecked("token")));
+        assert_eq!(room.player2, Some(Addr::unchecked("player2")));
+        assert_eq!(room.total_coins_raised, Uint128::new(50));
+        assert_eq!(room.game_state, GameState::InProgress);
+    }
+
+    #[test]
+    fn challenge_test() {
+        let mut deps = init();
+        let expires_at = Expiration::AtHeight(mock_env().block.height + 100);
+        send_challenge(&mut deps, expires_at).unwrap();
+        assert_eq!(game_status(&deps).challenge_expires, Some(expires_at));
+        let msg = HandleMsg::Challenge {
+            opponent: "player1".to_string(),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            expires_at,
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &coins(10, "ioc")), msg);
+        assert!(res.is_err());
+        let msg = HandleMsg::Challenge {
+            opponent: "Player2".to_string(),
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            expires_at,
+            variant: Variant::Classic,
+            board: None,
+            misere: false,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &coins(10, "ioc")), msg);
+        assert!(res.is_err());
+
+        // Player2 can't slip into the game without accepting.
+        let deposit = HandleMsg::Deposit { room_number: 0 };
+        let info = mock_info("player2", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), info.clone(), deposit).is_err());
+        let msg = HandleMsg::AcceptChallenge { room_id: 0 };
+        let outsider = mock_info("anyone", &coins(10, "ioc"));
+        let res = execute(deps.as_mut(), mock_env(), outsider, msg.clone());
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        let wrong_stake = mock_info("player2", &coins(5, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), wrong_stake, msg.clone()).is_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert!(execute(deps.as_mut(), mock_env(), info, msg).is_err());
+
+        let status = game_status(&deps);
+        assert_eq!(status.game_state, GameState::InProgress);
+        assert_eq!(status.challenge_expires, None);
+        assert_eq!(status.pot, Uint128::new(20));
+        let msg = HandleMsg::DeclineChallenge { room_id: 0 };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
+    }
+
+    #[test]
+    fn decline_challenge_test() {
+        let mut deps = init();
+        send_challenge(&mut deps, Expiration::AtHeight(mock_env().block.height + 100)).unwrap();
+
+        let msg = HandleMsg::DeclineChallenge { room_id: 0 };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg.clone());
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+
+        let status = game_status(&deps);
+        assert_eq!(status.game_state, GameState::Abandoned);
+        assert_eq!(status.pot, Uint128::zero());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player2", &[]), msg).is_err());
+    }
+
+    #[test]
+    fn expired_challenge_test() {
+        let mut deps = init();
+        let height = mock_env().block.height;
+        assert!(send_challenge(&mut deps, Expiration::AtHeight(height)).is_err());
+        send_challenge(&mut deps, Expiration::AtHeight(height + 100)).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 100;
+        let msg = HandleMsg::AcceptChallenge { room_id: 0 };
+        let info = mock_info("player2", &coins(10, "ioc"));
+        assert!(execute(deps.as_mut(), env.clone(), info, msg).is_err());
+
+        let msg = HandleMsg::CancelRoom { room_id: 0 };
+        let res = execute(deps.as_mut(), env, mock_info("anyone", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+        assert_eq!(game_status(&deps).game_state, GameState::Abandoned);
+    }
+
+    fn enqueue_for_match(
+        deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>,
+        player: &str,
+        variant: Variant,
+    ) -> Result<Option<u64>, ContractError> {
+        let msg = HandleMsg::EnqueueForMatch {
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            variant,
+            move_timeout: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(player, &coins(10, "ioc")), msg)?;
+        Ok(from_binary(&res.data.unwrap()).unwrap())
+    }
+
+    fn queue_status(deps: &OwnedDeps<MemoryStorage, MockApi, MockQuerier>, player: &str) -> Option<QueueEntry> {
+        let msg = QueryMsg::QueueStatus { player: player.to_string() };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        from_binary::<QueueStatusResponse>(&res).unwrap().entry
+    }
+
+    #[test]
+    fn matchmaking_test() {
+        let mut deps = init();
+        assert_eq!(enqueue_for_match(&mut deps, "player1", Variant::Classic).unwrap(), None);
+        assert_eq!(queue_status(&deps, "player1").unwrap().stake, Uint128::new(10));
+        assert!(enqueue_for_match(&mut deps, "player1", Variant::Classic).is_err());
+        assert_eq!(enqueue_for_match(&mut deps, "player3", Variant::Wild).unwrap(), None);
+
+        let msg = HandleMsg::EnqueueForMatch {
+            denom: Denom::Native("ioc".to_string()),
+            stake: Uint128::new(10),
+            variant: Variant::Classic,
+            move_timeout: None,
+        };
+        let wrong_stake = mock_info("player2", &coins(5, "ioc"));
+        assert!(execute(deps.as_mut(), mock_env(), wrong_stake, msg).is_err());
+        assert_eq!(enqueue_for_match(&mut deps, "player2", Variant::Classic).unwrap(), Some(0));
+
+        let room = Room::load_room(0, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.player1, Addr::unchecked("player1"));
+        assert_eq!(room.player2, Some(Addr::unchecked("player2")));
+        assert_eq!(room.game_state, GameState::InProgress);
+        assert_eq!(room.total_coins_raised, Uint128::new(20));
+        assert_eq!(queue_status(&deps, "player1"), None);
+        assert_eq!(queue_status(&deps, "player2"), None);
+        assert_eq!(queue_status(&deps, "player3").unwrap().variant, Variant::Wild);
+
+        // A player who was just matched can queue again.
+        assert_eq!(enqueue_for_match(&mut deps, "player1", Variant::Wild).unwrap(), Some(1));
+        let room = Room::load_room(1, &deps.storage).unwrap().unwrap();
+        assert_eq!(room.player1, Addr::unchecked("player3"));
+        assert_eq!(room.variant, Variant::Wild);
+    }
+
+    #[test]
+    fn leave_queue_test() {
+        let mut deps = init();
+        enqueue_for_match(&mut deps, "player1", Variant::Classic).unwrap();
+        let order_and_chaos = Variant::OrderAndChaos { order: Addr::unchecked("player2") };
+        assert!(enqueue_for_match(&mut deps, "player2", order_and_chaos).is_err());
+
+        let msg = HandleMsg::LeaveQueue {};
+        let res = execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 10)]);
+        assert_eq!(queue_status(&deps, "player1"), None);
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("player1", &[]), msg).is_err());
+
+        assert_eq!(enqueue_for_match(&mut deps, "player2", Variant::Classic).unwrap(), None);
+    }
+
+    fn env_after(seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env.block.height += seconds / 5;
+        env
+    }
+
+    #[test]
+    fn quantum_timeout_test() {
+        let mut deps = init();
+        start_test_room(&mut deps, Variant::Quantum, None, 0, false, Some(60)).unwrap();
+        let results = play_actions(&mut deps, &[spooky((0, 0), (0, 1)), spooky((1, 0), (1, 1)), spooky((0, 1), (0, 0))]);
+        assert!(results.iter().all(|res| res.is_ok()));
+        let deadline = env_after(60).block.time;
+        assert_eq!(game_status(&deps).move_deadline, Some(deadline));
+
+        // Collapsing doesn't restart player2's clock, the move after it is still due.
+        let collapse = HandleMsg::PlayMove { room_number: 0, action: Move::Collapse { coordinates: Coordinates::new(0, 0) } };
+        execute(deps.as_mut(), env_after(50), mock_info("player2", &[]), collapse).unwrap();
+        let status = game_status(&deps);
+        assert_eq!(status.last_move.unwrap().time, env_after(50).block.time);
+        assert_eq!(status.move_deadline, Some(deadline));
+
+        let msg = HandleMsg::ClaimTimeout { room_id: 0 };
+        assert!(execute(deps.as_mut(), env_after(59), mock_info("player1", &[]), msg.clone()).is_err());
+        execute(deps.as_mut(), env_after(61), mock_info("player1", &[]), msg).unwrap();
+        assert_eq!(game_status(&deps).winner, Some(Addr::unchecked("player1")));
+    }
+
+    #[test]
+    fn claim_timeout_test() {
+        let mut deps = init();
+        assert!(add_test_room(&mut deps, Variant::Classic, None, 10, false, Some(0)).is_err());
+        add_test_room(&mut deps, Variant::Classic, None, 10, false, Some(60)).unwrap();
+        let info = mock_info("player1", &coins(10, "ioc"));
+        execute(deps.as_mut(), mock_env(), info, HandleMsg::Deposit { room_number: 0 }).unwrap();
+        // The clock only starts once both stakes are in.
+        assert_eq!(game_status(&deps).move_deadline, None);
+        let info = mock_info("player2", &coins(10, "ioc"));
+        execute(deps.as_mut(), env_after(10), info, HandleMsg::AcceptChallenge { room_id: 0 }).unwrap();
+        let start = env_after(10).block.time;
+        assert_eq!(game_status(&deps).move_deadline, Some(start.plus_seconds(60)));
+
+        let msg = HandleMsg::ClaimTimeout { room_id: 0 };
+        let on_turn = mock_info("player1", &[]);
+        let res = execute(deps.as_mut(), env_after(100), on_turn, msg.clone());
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+        let res = execute(deps.as_mut(), env_after(100), mock_info("anyone", &[]), msg.clone());
+        assert!(matches!(res, Err(ContractError::Unauthorized {})));
+
+        let play = HandleMsg::PlayMove {
+            room_number: 0,
+            action: Move::Place { coordinates: Coordinates::new(1, 1) },
+        };
+        execute(deps.as_mut(), env_after(40), mock_info("player1", &[]), play).unwrap();
+        let status = game_status(&deps);
+        assert_eq!(status.last_move.unwrap().time, env_after(40).block.time);
+        assert_eq!(status.move_deadline, Some(env_after(100).block.time));
+
+        let player1_info = mock_info("player1", &[]);
+        assert!(execute(deps.as_mut(), env_after(99), player1_info.clone(), msg.clone()).is_err());
+        let res = execute(deps.as_mut(), env_after(100), player1_info.clone(), msg.clone()).unwrap();
+        assert_eq!(res.messages, vec![bank_send("player1", 20)]);
+
+        let status = game_status(&deps);
+        assert_eq!(status.game_state, GameState::TimedOut { player: Addr::unchecked("player2") });
+        assert_eq!(status.winner, Some(Addr::unchecked("player1")));
+        assert_eq!(status.move_deadline, None);
+        assert!(execute(deps.as_mut(), env_after(200), player1_info, msg).is_err());
+    }
+
+    #[test]
+    fn untimed_room_test() {
+        let mut deps = add_room_init();
+        assert_eq!(game_status(&deps).move_deadline, None);
+        let msg = HandleMsg::ClaimTimeout { room_id: 0 };
+        let player2_info = mock_info("player2", &[]);
+        assert!(execute(deps.as_mut(), env_after(1_000_000), player2_info, msg).is_err());
     }
 }
 
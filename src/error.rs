@@ -18,4 +18,7 @@ pub enum ContractError {
 
     #[error("State error: {val:?}")]
     StateError { val: String },
+
+    #[error("Migration error: {val:?}")]
+    MigrationError { val: String },
 }
@@ -0,0 +1,192 @@
+use crate::{
+    board::Board,
+    room::{rooms, GameState, Room},
+    state::{State, STATE},
+    variant::Variant,
+    ContractError,
+};
+use cosmwasm_std::{from_slice, Addr, Order, StdResult, Storage, Uint128};
+use cw20::Denom;
+use cw_storage_plus::Map;
+use serde::{Deserialize, Serialize};
+
+/// Key the pre-versioning contract stored its `State` under.
+pub const LEGACY_CONFIG_KEY: &[u8] = b"config";
+/// The only denom the pre-versioning contract accepted.
+pub const LEGACY_DENOM: &str = "ioc";
+
+/// `State` as stored by deployments that predate contract versioning.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LegacyState {
+    pub room_count: u8,
+    pub balance: Uint128,
+}
+
+/// `Room` as stored by deployments that predate contract versioning, under the raw key `[room_number]`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LegacyRoom {
+    pub player1: Addr,
+    pub player2: Addr,
+    pub turn: Addr,
+    pub board: Board,
+    pub game_state: String,
+    pub no_moves: u8,
+    pub total_coins_raised: Uint128,
+}
+
+impl TryFrom<LegacyRoom> for Room {
+    type Error = ContractError;
+
+    fn try_from(legacy: LegacyRoom) -> Result<Self, Self::Error> {
+        // Legacy rooms had no agreed stake, the coins paid per move stay in escrow as the pot
+        // of running games. Won games were paid out already, the coins of tied ones go to the treasury.
+        let mut room = Room::new(
+            legacy.player1,
+            Some(legacy.player2),
+            Denom::Native(LEGACY_DENOM.to_string()),
+            Uint128::zero(),
+            Variant::Classic,
+            legacy.board,
+        );
+        room.game_state = parse_game_state(&legacy.game_state, &room.board)?;
+        room.turn = legacy.turn;
+        room.no_moves = legacy.no_moves.into();
+        if !room.is_finished() {
+            room.total_coins_raised = legacy.total_coins_raised;
+        }
+        Ok(room)
+    }
+}
+
+/// `Room` as stored by versions before 0.4.0, with `game_state` kept as a `Debug` string.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UntypedStateRoom {
+    pub player1: Addr,
+    pub player2: Addr,
+    pub turn: Addr,
+    pub board: Board,
+    pub game_state: String,
+    pub no_moves: u8,
+    pub denom: Denom,
+    pub stake: Uint128,
+    pub player1_deposited: bool,
+    pub player2_deposited: bool,
+    pub total_coins_raised: Uint128,
+}
+
+impl TryFrom<UntypedStateRoom> for Room {
+    type Error = ContractError;
+
+    fn try_from(untyped: UntypedStateRoom) -> Result<Self, Self::Error> {
+        let game_state = parse_game_state(&untyped.game_state, &untyped.board)?;
+        // Finished games were paid out or refunded, only running ones still hold a pot.
+        let total_coins_raised = match game_state {
+            GameState::WaitingForOpponent | GameState::InProgress => untyped.total_coins_raised,
+            _ => Uint128::zero(),
+        };
+        Ok(Room {
+            game_state,
+            player1: untyped.player1,
+            player2: Some(untyped.player2),
+            turn: untyped.turn,
+            board: untyped.board,
+            no_moves: untyped.no_moves.into(),
+            denom: untyped.denom,
+            stake: untyped.stake,
+            player1_deposited: untyped.player1_deposited,
+            player2_deposited: untyped.player2_deposited,
+            total_coins_raised,
+            last_move: None,
+            variant: Variant::Classic,
+            boards: vec![],
+            next_board: None,
+            dead_boards: vec![],
+            positions: vec![],
+            player1_numbers: vec![],
+            player2_numbers: vec![],
+            pending_collapse: None,
+            misere: false,
+            challenge_expires: None,
+            move_timeout: None,
+            turn_started: None,
+            house_fee_bps: 0,
+        })
+    }
+}
+
+/// Parses the `Debug` output the old `GameState` was stored as, e.g. `GameWon { player: Addr("..") }`.
+pub fn parse_game_state(game_state: &str, board: &Board) -> Result<GameState, ContractError> {
+    let winner = game_state
+        .strip_prefix("GameWon { player: Addr(\"")
+        .and_then(|rest| rest.strip_suffix("\") }"));
+    match (game_state, winner) {
+        ("WaitingForDeposits", _) => Ok(GameState::WaitingForOpponent),
+        ("InProgess", _) => Ok(GameState::InProgress),
+        ("Tie", _) => Ok(GameState::Tie),
+        (_, Some(winner)) => {
+            let player = Addr::unchecked(winner);
+            // The winning line wasn't stored, find it again among the winner's cells.
+            let line = board
+                .cells
+                .iter()
+                .filter(|cell| cell.player.as_ref() == Some(&player))
+                .find_map(|cell| board.winning_line(cell.coordinates.clone()))
+                .unwrap_or_default();
+            Ok(GameState::Won { player, line })
+        }
+        _ => Err(ContractError::MigrationError {
+            val: format!("Unknown game state {}.", game_state),
+        }),
+    }
+}
+
+/// Rewrites rooms stored with a string `game_state` to the typed `GameState`.
+pub fn migrate_game_states(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    // Written through a plain map, loading the old value as `Room` for the indexes would fail.
+    let untyped_rooms: Map<u64, UntypedStateRoom> = Map::new("rooms");
+    let typed_rooms: Map<u64, Room> = Map::new("rooms");
+    let all_rooms = untyped_rooms
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(u64, UntypedStateRoom)>>>()?;
+    for (room_number, untyped) in all_rooms {
+        typed_rooms.save(storage, room_number, &Room::try_from(untyped)?)?;
+    }
+    Ok(())
+}
+
+/// Moves a pre-versioning deployment's state and rooms into the current layout.
+pub fn migrate_legacy_layout(storage: &mut dyn Storage, admin: Addr) -> Result<(), ContractError> {
+    let legacy_state: LegacyState = match storage.get(LEGACY_CONFIG_KEY) {
+        Some(data) => from_slice(&data)?,
+        None => {
+            return Err(ContractError::MigrationError {
+                val: "No legacy state found.".to_string(),
+            })
+        }
+    };
+
+    let mut unclaimed = legacy_state.balance;
+    for room_number in 0..legacy_state.room_count {
+        let key = [room_number];
+        let legacy_room = storage.get(&key).map(|data| from_slice::<LegacyRoom>(&data));
+        if let Some(legacy_room) = legacy_room {
+            let legacy_room = legacy_room?;
+            // The legacy contract paid nothing out on a tie, so nobody can claim these coins.
+            if legacy_room.game_state == "Tie" {
+                unclaimed += legacy_room.total_coins_raised;
+            }
+            rooms().save(storage, room_number.into(), &Room::try_from(legacy_room)?)?;
+            storage.remove(&key);
+        }
+    }
+
+    let denom = Denom::Native(LEGACY_DENOM.to_string());
+    let mut state = State::new(admin, 0, vec![LEGACY_DENOM.to_string()], vec![]);
+    state.room_count = legacy_state.room_count.into();
+    if !unclaimed.is_zero() {
+        state.add_to_treasury(&denom, unclaimed);
+    }
+    // The legacy state sits under the same key, so it is overwritten in place.
+    STATE.save(storage, &state)?;
+    Ok(())
+}
@@ -2,9 +2,12 @@ pub mod board;
 pub mod cell;
 pub mod contract;
 mod error;
+pub mod legacy;
 pub mod msg;
+pub mod quantum;
+pub mod queue;
 pub mod room;
-pub mod type_helpers;
 pub mod state;
+pub mod variant;
 
 pub use crate::error::ContractError;
@@ -1,23 +1,188 @@
-use crate::{cell::Coordinates, room::Room};
+use crate::{
+    board::BoardConfig,
+    cell::Coordinates,
+    queue::QueueEntry,
+    room::{GameState, LastMove, Room, RoomStatus},
+    state::TreasuryBalance,
+    variant::{Move, Variant},
+};
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cw20::{Cw20ReceiveMsg, Denom, Expiration};
 
 #[cw_serde]
-pub struct InitMsg {}
+pub struct InitMsg {
+    /// Defaults to the instantiating address.
+    pub admin: Option<String>,
+    #[serde(default)]
+    pub house_fee_bps: u16,
+    pub accepted_denoms: Vec<String>,
+    #[serde(default)]
+    pub accepted_cw20: Vec<String>,
+}
 
+#[cw_serde]
+pub struct MigrateMsg {
+    /// Admin to set when upgrading a deployment that predates contract versioning.
+    pub admin: Option<String>,
+}
 
 #[cw_serde(Serialize)]
 pub enum HandleMsg {
+    /// Plays `action`, which has to be a kind of move the room's variant accepts.
     PlayMove {
-        coordinates: Coordinates,
-        room_number: u8,
+        room_number: u64,
+        action: Move,
     },
     RestartGame {
-        room_number: u8,
+        room_number: u64,
+    },
+    /// Gives up a running game, the opponent wins the pot.
+    Resign {
+        room_number: u64,
+    },
+    /// Invites `player2` to a room that starts once they accept it with `AcceptChallenge`
+    /// and each player deposited `stake` of `denom`. The invitation doesn't expire.
+    /// Only `player1` can send it, and may attach their deposit to a native room.
+    AddRoom {
+        player1: Addr,
+        player2: Addr,
+        denom: Denom,
+        stake: Uint128,
+        #[serde(default)]
+        variant: Variant,
+        /// Defaults to the variant's board, 3x3 with three in a row for classic rooms.
+        board: Option<BoardConfig>,
+        /// Completing a line loses instead of wins.
+        #[serde(default)]
+        misere: bool,
+        /// Seconds each move may take, unlimited if left out.
+        move_timeout: Option<u64>,
+    },
+    Deposit {
+        room_number: u64,
+    },
+    /// Opens a room with the sender as player1, waiting for anyone to join.
+    /// A native stake has to be attached, CW20 rooms are opened through `Receive`.
+    CreateRoom {
+        denom: Denom,
+        stake: Uint128,
+        #[serde(default)]
+        variant: Variant,
+        board: Option<BoardConfig>,
+        #[serde(default)]
+        misere: bool,
+        move_timeout: Option<u64>,
+    },
+    /// Takes the second seat of an open room, with the room's stake attached.
+    JoinRoom {
+        room_id: u64,
+    },
+    /// Calls off a room that hasn't started, deposits go back to the players who made them.
+    /// Either player can cancel, anyone can close a challenge that expired without being accepted.
+    CancelRoom {
+        room_id: u64,
+    },
+    /// Invites `opponent` to a game only they can accept, until `expires_at`.
+    /// A native stake has to be attached, CW20 challenges are sent through `Receive`.
+    Challenge {
+        opponent: String,
+        denom: Denom,
+        stake: Uint128,
+        expires_at: Expiration,
+        #[serde(default)]
+        variant: Variant,
+        board: Option<BoardConfig>,
+        #[serde(default)]
+        misere: bool,
+        move_timeout: Option<u64>,
+    },
+    /// Accepts a challenge or an `AddRoom` invitation, with the room's stake attached.
+    AcceptChallenge {
+        room_id: u64,
+    },
+    /// Turns a challenge down, the challenger's stake is refunded.
+    DeclineChallenge {
+        room_id: u64,
+    },
+    /// Waits for an opponent staking the same and wanting the same variant, played on its
+    /// default board. The oldest such entry is paired right away if there is one.
+    /// The contract keeps no player ratings, so pairing ignores skill.
+    /// A native stake has to be attached, CW20 stakes are queued through `Receive`.
+    EnqueueForMatch {
+        denom: Denom,
+        stake: Uint128,
+        #[serde(default)]
+        variant: Variant,
+        /// Only entries with the same move timeout are paired.
+        move_timeout: Option<u64>,
+    },
+    /// Leaves the queue, the stake is refunded.
+    LeaveQueue {},
+    /// Wins the pot for the sender once the opponent let the move timeout pass on their turn.
+    ClaimTimeout {
+        room_id: u64,
     },
+    /// Entry point for CW20 stakes, the embedded message is a `ReceiveMsg`.
+    Receive(Cw20ReceiveMsg),
+    /// Sends collected house fees out of the treasury. Admin only.
+    Withdraw {
+        denom: Denom,
+        amount: Uint128,
+        recipient: String,
+    },
+    /// Admin only, fields left out keep their current value.
+    UpdateConfig {
+        admin: Option<String>,
+        house_fee_bps: Option<u16>,
+        accepted_denoms: Option<Vec<String>>,
+        accepted_cw20: Option<Vec<String>>,
+    },
+}
+
+/// Messages embedded in a CW20 `Send`, the sent tokens are the sender's deposit.
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Creates a room staked in the sent token, with the sent amount as the stake.
+    /// The tokens have to come from `player1`.
     AddRoom {
         player1: Addr,
         player2: Addr,
+        #[serde(default)]
+        variant: Variant,
+        board: Option<BoardConfig>,
+        #[serde(default)]
+        misere: bool,
+        move_timeout: Option<u64>,
+    },
+    Deposit { room_number: u64 },
+    /// Opens a room staked in the sent token, with the sender as player1.
+    CreateRoom {
+        #[serde(default)]
+        variant: Variant,
+        board: Option<BoardConfig>,
+        #[serde(default)]
+        misere: bool,
+        move_timeout: Option<u64>,
+    },
+    JoinRoom { room_id: u64 },
+    /// Challenges `opponent` to a game staked in the sent token.
+    Challenge {
+        opponent: String,
+        expires_at: Expiration,
+        #[serde(default)]
+        variant: Variant,
+        board: Option<BoardConfig>,
+        #[serde(default)]
+        misere: bool,
+        move_timeout: Option<u64>,
+    },
+    AcceptChallenge { room_id: u64 },
+    /// Queues for a game staked in the sent token.
+    EnqueueForMatch {
+        #[serde(default)]
+        variant: Variant,
+        move_timeout: Option<u64>,
     },
 }
 
@@ -25,15 +190,34 @@ pub enum HandleMsg {
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(TableStatusResponse)]
-    BoardStatus { room_number: u8 },
+    BoardStatus { room_number: u64 },
     #[returns(PlayerTurnResponse)]
-    PlayerTurn { room_number: u8 },
+    PlayerTurn { room_number: u64 },
     #[returns(GameStatusResponse)]
-    GameStatus { room_number: u8 },
+    GameStatus { room_number: u64 },
     #[returns(RoomExistResponse)]
-    RoomExist { room_number: u8 },
-    #[returns(ListPageResponse)]
-    RoomList { page_number: u8 },
+    RoomExist { room_number: u64 },
+    /// Existing rooms ordered by room id, starting after the `start_after` cursor.
+    /// A filtered page can come back short or empty, keep going from `next`.
+    #[returns(RoomsResponse)]
+    RoomList {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        status_filter: Option<RoomStatus>,
+    },
+    #[returns(ConfigResponse)]
+    Config {},
+    /// Rooms where `player` holds either seat, ordered by room id, paged like `RoomList`.
+    #[returns(RoomsResponse)]
+    RoomsByPlayer {
+        player: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        status_filter: Option<RoomStatus>,
+    },
+    /// Whether `player` is waiting in the matchmaking queue.
+    #[returns(QueueStatusResponse)]
+    QueueStatus { player: String },
 }
 
 // We define a custom struct for each query response
@@ -49,13 +233,51 @@ pub struct PlayerTurnResponse {
 }
 #[cw_serde(Serialize)]
 pub struct GameStatusResponse {
-    pub status: String,
+    pub game_state: GameState,
+    pub winner: Option<Addr>,
+    pub winning_line: Option<Vec<Coordinates>>,
+    pub no_moves: u16,
+    /// Player to move, `None` once the game is over.
+    pub turn: Option<Addr>,
+    pub denom: Denom,
+    /// Stakes currently held in escrow.
+    pub pot: Uint128,
+    pub last_move: Option<LastMove>,
+    pub variant: Variant,
+    pub misere: bool,
+    /// Sub-board the player to move is bound to in ultimate rooms.
+    pub next_board: Option<u8>,
+    /// When a challenge nobody accepted yet lapses.
+    pub challenge_expires: Option<Expiration>,
+    /// When the player to move runs out of time, in rooms with a move timeout.
+    pub move_deadline: Option<Timestamp>,
+}
+#[cw_serde(Serialize)]
+pub struct QueueStatusResponse {
+    /// `None` once the player was matched or left.
+    pub entry: Option<QueueEntry>,
 }
 #[cw_serde(Serialize)]
 pub struct RoomExistResponse {
     pub room_exist: bool,
 }
 #[cw_serde(Serialize)]
-pub struct ListPageResponse {
-    pub list_rooms: Vec<Option<Room>>,
+pub struct RoomInfo {
+    pub room_id: u64,
+    pub room: Room,
+}
+#[cw_serde(Serialize)]
+pub struct RoomsResponse {
+    pub rooms: Vec<RoomInfo>,
+    /// Room id to pass as `start_after` for the next page, `None` once every room was scanned.
+    #[serde(default)]
+    pub next: Option<u64>,
+}
+#[cw_serde(Serialize)]
+pub struct ConfigResponse {
+    pub admin: Addr,
+    pub house_fee_bps: u16,
+    pub accepted_denoms: Vec<String>,
+    pub accepted_cw20: Vec<Addr>,
+    pub treasury: Vec<TreasuryBalance>,
 }
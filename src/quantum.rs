@@ -0,0 +1,138 @@
+use cosmwasm_std::Addr;
+
+use crate::{
+    board::Board,
+    cell::{Coordinates, SpookyMark},
+    ContractError,
+};
+
+impl Board {
+    /// Places a spooky mark in both cells and returns whether it closed a cycle of entangled cells.
+    pub fn add_spooky_mark(
+        &mut self,
+        player: &Addr,
+        sign: &str,
+        subscript: u8,
+        first: &Coordinates,
+        second: &Coordinates,
+    ) -> Result<bool, ContractError> {
+        let (first_index, second_index) = match (self.index(first), self.index(second)) {
+            (Some(first_index), Some(second_index)) => (first_index, second_index),
+            _ => {
+                return Err(ContractError::CustomError {
+                    val: "Coordinates are off the board.".to_string(),
+                })
+            }
+        };
+        if first_index == second_index {
+            return Err(ContractError::CustomError {
+                val: "A spooky mark goes into two different cells.".to_string(),
+            });
+        }
+        if self.cells[first_index].player.is_some() || self.cells[second_index].player.is_some() {
+            return Err(ContractError::CustomError {
+                val: "Spot is occupied".to_string(),
+            });
+        }
+
+        let cycle = self.is_entangled(first_index, second_index);
+        for (cell_index, partner_index) in [(first_index, second_index), (second_index, first_index)] {
+            let partner = self.cells[partner_index].coordinates.clone();
+            self.cells[cell_index].spooky.push(SpookyMark {
+                player: player.clone(),
+                sign: sign.to_string(),
+                subscript,
+                partner,
+            });
+        }
+        Ok(cycle)
+    }
+    // Whether spooky marks already link the two cells.
+    fn is_entangled(&self, from: usize, to: usize) -> bool {
+        let mut seen = vec![false; self.cells.len()];
+        let mut stack = vec![from];
+        while let Some(cell_index) = stack.pop() {
+            if cell_index == to {
+                return true;
+            }
+            if seen[cell_index] {
+                continue;
+            }
+            seen[cell_index] = true;
+            for mark in &self.cells[cell_index].spooky {
+                stack.extend(self.index(&mark.partner));
+            }
+        }
+        false
+    }
+    /// Turns the mark placed on move `subscript` into a classical mark at `coordinates`.
+    /// Marks sharing a cell with a classical one are pushed into their other cell, and so on.
+    pub fn collapse(&mut self, subscript: u8, coordinates: &Coordinates) -> Result<(), ContractError> {
+        let cell_index = self
+            .index(coordinates)
+            .filter(|cell_index| {
+                self.cells[*cell_index]
+                    .spooky
+                    .iter()
+                    .any(|mark| mark.subscript == subscript)
+            });
+        let cell_index = match cell_index {
+            Some(cell_index) => cell_index,
+            None => {
+                return Err(ContractError::CustomError {
+                    val: format!("Mark {} is not in that cell.", subscript),
+                })
+            }
+        };
+
+        let mut pending = vec![(subscript, cell_index)];
+        while let Some((subscript, cell_index)) = pending.pop() {
+            let cell = &mut self.cells[cell_index];
+            let mark = match cell.spooky.iter().find(|mark| mark.subscript == subscript) {
+                Some(mark) if cell.player.is_none() => mark.clone(),
+                _ => continue,
+            };
+            let others = std::mem::take(&mut cell.spooky);
+            cell.player = Some(mark.player);
+            cell.sign = mark.sign;
+            cell.value = Some(subscript);
+
+            if let Some(partner_index) = self.index(&mark.partner) {
+                self.cells[partner_index]
+                    .spooky
+                    .retain(|other| other.subscript != subscript);
+            }
+            for other in others.into_iter().filter(|other| other.subscript != subscript) {
+                pending.extend(self.index(&other.partner).map(|partner_index| (other.subscript, partner_index)));
+            }
+        }
+        Ok(())
+    }
+    /// Player with a line of classical marks and that line. When both players have one,
+    /// the line whose latest mark came first wins.
+    pub fn classical_winner(&self) -> Option<(Addr, Vec<Coordinates>)> {
+        let mut best: Option<(u8, Addr, Vec<Coordinates>)> = None;
+        for line in self.lines() {
+            let cells = line
+                .iter()
+                .map(|coordinates| &self.cells[self.index(coordinates).unwrap()])
+                .collect::<Vec<_>>();
+            let player = match &cells[0].player {
+                Some(player) => player,
+                None => continue,
+            };
+            if cells.iter().any(|cell| cell.player.as_ref() != Some(player)) {
+                continue;
+            }
+            let latest = cells.iter().filter_map(|cell| cell.value).max().unwrap_or_default();
+            if best.as_ref().is_none_or(|(best_latest, ..)| latest < *best_latest) {
+                best = Some((latest, player.clone(), line));
+            }
+        }
+        best.map(|(_, player, line)| (player, line))
+    }
+    /// Cells that can still take a spooky mark.
+    pub fn open_cells(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.player.is_none()).count()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{state::State, variant::Variant, ContractError};
+use cosmwasm_std::{to_vec, Addr, Order, StdResult, Storage, Timestamp, Uint128};
+use cw20::Denom;
+use cw_storage_plus::{Index, IndexList, IndexedMap, KeyDeserialize, MultiIndex, UniqueIndex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A player waiting to be matched, their stake is held in escrow until then.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct QueueEntry {
+    pub player: Addr,
+    pub denom: Denom,
+    pub stake: Uint128,
+    pub variant: Variant,
+    pub move_timeout: Option<u64>,
+    pub enqueued_at: Timestamp,
+}
+
+pub struct QueueIndexes<'a> {
+    pub player: UniqueIndex<'a, Addr, QueueEntry, u64>,
+    pub pool: MultiIndex<'a, Vec<u8>, QueueEntry, u64>,
+}
+
+impl<'a> IndexList<QueueEntry> for QueueIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<QueueEntry>> + '_> {
+        let v: Vec<&dyn Index<QueueEntry>> = vec![&self.player, &self.pool];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Queue entries in the order they came in, indexed by player and by the games they can be paired for.
+pub fn queue<'a>() -> IndexedMap<'a, u64, QueueEntry, QueueIndexes<'a>> {
+    let indexes = QueueIndexes {
+        player: UniqueIndex::new(|entry| entry.player.clone(), "queue__player"),
+        pool: MultiIndex::new(|_pk, entry| entry.pool(), "queue", "queue__pool"),
+    };
+    IndexedMap::new("queue", indexes)
+}
+
+impl QueueEntry {
+    /// Entries with the same pool are staked alike and want the same variant and move timeout.
+    fn pool(&self) -> Vec<u8> {
+        // Serializing plain data can't fail.
+        to_vec(&(&self.denom, self.stake, &self.variant, self.move_timeout)).unwrap_or_default()
+    }
+    pub fn load_by_player(storage: &dyn Storage, player: &Addr) -> StdResult<Option<(u64, QueueEntry)>> {
+        let entry = queue().idx.player.item(storage, player.clone())?;
+        entry
+            .map(|(entry_id, entry)| Ok((u64::from_vec(entry_id)?, entry)))
+            .transpose()
+    }
+    /// Oldest entry this one can be paired with, there are no ratings to narrow it down by.
+    pub fn find_match(&self, storage: &dyn Storage) -> StdResult<Option<(u64, QueueEntry)>> {
+        queue()
+            .idx
+            .pool
+            .prefix(self.pool())
+            .range(storage, None, None, Order::Ascending)
+            .next()
+            .transpose()
+    }
+    pub fn enqueue(&self, storage: &mut dyn Storage) -> Result<u64, ContractError> {
+        let mut state = State::load_state(storage)?;
+        let entry_id = state.queue_count;
+        queue().save(storage, entry_id, self)?;
+        state.queue_count += 1;
+        state.save_state(storage)?;
+        Ok(entry_id)
+    }
+    pub fn remove(storage: &mut dyn Storage, entry_id: u64) -> StdResult<()> {
+        queue().remove(storage, entry_id)
+    }
+}
@@ -1,95 +1,829 @@
-use core::fmt;
-use crate::{board::Board, state::State, type_helpers::may_deserialize, ContractError};
-use cosmwasm_std::{to_vec, Addr, Storage, Uint128};
+use crate::{
+    board::Board,
+    cell::Coordinates,
+    state::{State, BPS_DENOMINATOR},
+    variant::{
+        Move, Variant, MORRIS_MOVE_LIMIT, MORRIS_PIECES, MORRIS_REPETITIONS, NUMERICAL_TOTAL,
+    },
+    ContractError,
+};
+use cosmwasm_std::{Addr, BlockInfo, Order, StdResult, Storage, Timestamp, Uint128};
+use cw20::{Denom, Expiration};
+use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum GameState {
-    GameWon { player: Addr },
+    /// Waiting for the players' stakes before the first move.
+    WaitingForOpponent,
+    InProgress,
+    Won { player: Addr, line: Vec<Coordinates> },
     Tie,
-    InProgess,
+    /// Closed before it started, stakes were refunded.
+    Abandoned,
+    /// `player` gave up and the opponent won the pot.
+    Resigned { player: Addr },
+    /// `player` ran out of time for a move and the opponent claimed the pot.
+    TimedOut { player: Addr },
 }
-impl fmt::Display for GameState {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
+
+/// Status of a room as seen by clients filtering room lists.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomStatus {
+    Open,
+    InProgress,
+    Won,
+    Tie,
+    Abandoned,
 }
 
+/// Block at which a move was played.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LastMove {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+impl From<&BlockInfo> for LastMove {
+    fn from(block: &BlockInfo) -> Self {
+        Self {
+            height: block.height,
+            time: block.time,
+        }
+    }
+}
+
+pub struct RoomIndexes<'a> {
+    pub player1: MultiIndex<'a, Addr, Room, u64>,
+    pub player2: MultiIndex<'a, Addr, Room, u64>,
+}
+
+impl<'a> IndexList<Room> for RoomIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Room>> + '_> {
+        let v: Vec<&dyn Index<Room>> = vec![&self.player1, &self.player2];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Rooms by id, indexed by both seats so a player's rooms can be listed without a full scan.
+/// Open rooms are indexed under an empty address for the free seat, which no player can have.
+pub fn rooms<'a>() -> IndexedMap<'a, u64, Room, RoomIndexes<'a>> {
+    let indexes = RoomIndexes {
+        player1: MultiIndex::new(|_pk, room| room.player1.clone(), "rooms", "rooms__player1"),
+        player2: MultiIndex::new(
+            |_pk, room| room.player2.clone().unwrap_or_else(|| Addr::unchecked("")),
+            "rooms",
+            "rooms__player2",
+        ),
+    };
+    IndexedMap::new("rooms", indexes)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Room {
     pub player1: Addr,
-    pub player2: Addr,
+    /// `None` while an open room waits for someone to join.
+    pub player2: Option<Addr>,
     pub turn: Addr,
     pub board: Board,
-    pub game_state: String,
-    pub no_moves: u8,
+    pub game_state: GameState,
+    pub no_moves: u16,
+    /// Native denomination or CW20 token the stakes are deposited and paid out in.
+    pub denom: Denom,
+    /// Amount each player has to deposit before the game starts.
+    pub stake: Uint128,
+    pub player1_deposited: bool,
+    pub player2_deposited: bool,
+    /// Deposits currently held in escrow for this room.
     pub total_coins_raised: Uint128,
+    #[serde(default)]
+    pub last_move: Option<LastMove>,
+    #[serde(default)]
+    pub variant: Variant,
+    /// Sub-boards of an ultimate room, `board` then holds who claimed each of them.
+    /// Notakto rooms are played on these, copies of `board`.
+    #[serde(default)]
+    pub boards: Vec<Board>,
+    /// Sub-board the player to move has to play in, any open one if `None`.
+    #[serde(default)]
+    pub next_board: Option<u8>,
+    /// Notakto boards that already have a line, in the order they died.
+    #[serde(default)]
+    pub dead_boards: Vec<u8>,
+    /// Positions reached by slides in a Three Men's Morris room, to spot repetitions.
+    #[serde(default)]
+    pub positions: Vec<String>,
+    /// Numbers each player already placed in a numerical room.
+    #[serde(default)]
+    pub player1_numbers: Vec<u8>,
+    #[serde(default)]
+    pub player2_numbers: Vec<u8>,
+    /// Quantum move that closed a cycle, the player to move has to collapse it first.
+    #[serde(default)]
+    pub pending_collapse: Option<u8>,
+    /// Misère rules, completing a line loses and the opponent takes the pot.
+    #[serde(default)]
+    pub misere: bool,
+    /// Set while player2 still has to accept the challenge, which lapses at this point.
+    #[serde(default)]
+    pub challenge_expires: Option<Expiration>,
+    /// Seconds each move may take, after which the waiting player can claim the pot.
+    #[serde(default)]
+    pub move_timeout: Option<u64>,
+    /// When the player to move got the turn.
+    #[serde(default)]
+    pub turn_started: Option<Timestamp>,
+    /// House fee in force when the room was created, later config changes don't touch it.
+    #[serde(default)]
+    pub house_fee_bps: u16,
 }
 
 impl Room {
-    pub fn new(player1: Addr, player2: Addr) -> Self {
+    pub fn new(
+        player1: Addr,
+        player2: Option<Addr>,
+        denom: Denom,
+        stake: Uint128,
+        variant: Variant,
+        board: Board,
+    ) -> Self {
+        let boards = match variant {
+            Variant::Ultimate => vec![board.clone(); board.cells.len()],
+            Variant::Notakto { boards } => vec![board.clone(); boards.into()],
+            _ => vec![],
+        };
+        let turn = match &variant {
+            Variant::OrderAndChaos { order } => order.clone(),
+            _ => player1.clone(),
+        };
         Self {
-            player1: player1.clone(),
+            game_state: Self::initial_state(stake, player2.is_some()),
+            player1,
             player2,
-            turn: player1,
-            board: Board::new(),
-            game_state: GameState::InProgess.to_string(),
+            turn,
+            board,
             no_moves: 0,
+            denom,
+            stake,
+            player1_deposited: false,
+            player2_deposited: false,
             total_coins_raised: Uint128::zero(),
+            last_move: None,
+            variant,
+            boards,
+            next_board: None,
+            dead_boards: vec![],
+            positions: vec![],
+            player1_numbers: vec![],
+            player2_numbers: vec![],
+            pending_collapse: None,
+            misere: false,
+            challenge_expires: None,
+            move_timeout: None,
+            turn_started: None,
+            house_fee_bps: 0,
         }
     }
     pub fn restart_game(&self) -> Self {
         Self {
             player1: self.player1.clone(),
             player2: self.player2.clone(),
-            turn: self.player1.clone(),
+            turn: match &self.variant {
+                Variant::OrderAndChaos { order } => order.clone(),
+                _ => self.player1.clone(),
+            },
             board: self.board.restart_board(),
-            game_state: GameState::InProgess.to_string(),
+            game_state: Self::initial_state(self.stake, self.player2.is_some()),
             no_moves: 0,
+            denom: self.denom.clone(),
+            stake: self.stake,
+            player1_deposited: false,
+            player2_deposited: false,
             total_coins_raised: Uint128::zero(),
+            last_move: None,
+            variant: self.variant.clone(),
+            boards: self.boards.iter().map(Board::restart_board).collect(),
+            next_board: None,
+            dead_boards: vec![],
+            positions: vec![],
+            player1_numbers: vec![],
+            player2_numbers: vec![],
+            pending_collapse: None,
+            misere: self.misere,
+            challenge_expires: None,
+            move_timeout: self.move_timeout,
+            turn_started: None,
+            house_fee_bps: self.house_fee_bps,
+        }
+    }
+    // Rooms without a stake have nothing to escrow and can start as soon as both seats are taken.
+    // Rooms naming player2 up front are turned into challenges, so only queue matches start seated.
+    fn initial_state(stake: Uint128, seated: bool) -> GameState {
+        if stake.is_zero() && seated {
+            GameState::InProgress
+        } else {
+            GameState::WaitingForOpponent
+        }
+    }
+    pub fn is_player(&self, address: &Addr) -> bool {
+        *address == self.player1 || Some(address) == self.player2.as_ref()
+    }
+    pub fn is_finished(&self) -> bool {
+        !matches!(
+            self.game_state,
+            GameState::WaitingForOpponent | GameState::InProgress
+        )
+    }
+    pub fn status(&self) -> RoomStatus {
+        match self.game_state {
+            GameState::WaitingForOpponent => RoomStatus::Open,
+            GameState::InProgress => RoomStatus::InProgress,
+            GameState::Won { .. } | GameState::Resigned { .. } | GameState::TimedOut { .. } => {
+                RoomStatus::Won
+            }
+            GameState::Tie => RoomStatus::Tie,
+            GameState::Abandoned => RoomStatus::Abandoned,
+        }
+    }
+    pub fn winner(&self) -> Option<Addr> {
+        match &self.game_state {
+            GameState::Won { player, .. } => Some(player.clone()),
+            GameState::Resigned { player } | GameState::TimedOut { player } => {
+                Some(self.opponent(player))
+            }
+            _ => None,
+        }
+    }
+    /// The other player, games are only played once both seats are taken.
+    pub fn opponent(&self, player: &Addr) -> Addr {
+        match &self.player2 {
+            Some(player2) if *player == self.player1 => player2.clone(),
+            _ => self.player1.clone(),
+        }
+    }
+    /// Takes the free seat of an open room, a stake still has to be deposited.
+    pub fn join(&mut self, player: &Addr) -> Result<(), ContractError> {
+        if self.player2.is_some() || self.game_state != GameState::WaitingForOpponent {
+            return Err(ContractError::RoomError {
+                val: "Room is not open.".to_string(),
+            });
+        }
+        if *player == self.player1 {
+            return Err(ContractError::RoomError {
+                val: "You can't join your own room.".to_string(),
+            });
+        }
+        self.player2 = Some(player.clone());
+        if self.stake.is_zero() {
+            self.game_state = GameState::InProgress;
+        }
+        Ok(())
+    }
+    /// Part of the pot kept by the house when the game is won.
+    pub fn house_fee(&self) -> Uint128 {
+        self.total_coins_raised
+            .multiply_ratio(self.house_fee_bps, BPS_DENOMINATOR)
+    }
+    /// Starts the clock of the player to move, once the game is under way.
+    pub fn start_turn(&mut self, block: &BlockInfo) {
+        if self.game_state == GameState::InProgress {
+            self.turn_started = Some(block.time);
+        }
+    }
+    /// When the player to move runs out of time, `None` in rooms without a move timeout.
+    pub fn move_deadline(&self) -> Option<Timestamp> {
+        match (self.move_timeout, self.turn_started) {
+            (Some(timeout), Some(started)) if self.game_state == GameState::InProgress => {
+                Some(started.plus_seconds(timeout))
+            }
+            _ => None,
+        }
+    }
+    /// Turns the room into a challenge player2 has to accept before `expires`.
+    pub fn challenge(&mut self, expires: Expiration) {
+        self.challenge_expires = Some(expires);
+        self.game_state = GameState::WaitingForOpponent;
+    }
+    /// Whether a challenge is pending and `block` is past its expiry.
+    pub fn is_challenge_expired(&self, block: &BlockInfo) -> bool {
+        self.challenge_expires
+            .as_ref()
+            .is_some_and(|expires| expires.is_expired(block))
+    }
+    pub fn accept_challenge(&mut self, player: &Addr, block: &BlockInfo) -> Result<(), ContractError> {
+        if self.challenge_expires.is_none() || self.game_state != GameState::WaitingForOpponent {
+            return Err(ContractError::RoomError {
+                val: "No challenge is pending.".to_string(),
+            });
+        }
+        if Some(player) != self.player2.as_ref() {
+            return Err(ContractError::Unauthorized {});
+        }
+        if self.is_challenge_expired(block) {
+            return Err(ContractError::RoomError {
+                val: "The challenge has expired.".to_string(),
+            });
         }
+        self.challenge_expires = None;
+        if self.stake.is_zero() {
+            self.game_state = GameState::InProgress;
+        }
+        Ok(())
+    }
+    /// Plays `mv` for `player` and ends the game if it made a line or filled the board.
+    /// The turn passes to the opponent, except after a collapse which is followed by a move.
+    pub fn play(&mut self, player: &Addr, mv: Move) -> Result<(), ContractError> {
+        let passes_turn = !matches!(mv, Move::Collapse { .. });
+        self.apply(player, mv)?;
+        if passes_turn {
+            self.turn = self.opponent(player);
+        }
+        Ok(())
     }
-    pub fn load_room(room_number: u8, storage: &dyn Storage) -> Option<Room> {
-        let key = room_number.to_be_bytes();
-        let room_exist = storage.get(&key);
-        if room_exist == None {
-            return None;
+    fn apply(&mut self, player: &Addr, mv: Move) -> Result<(), ContractError> {
+        match (&self.variant, mv) {
+            (Variant::Classic | Variant::Qubic, Move::Place { coordinates }) => {
+                self.place(player, coordinates, self.sign(player))
+            }
+            (Variant::Wild, Move::PlaceSign { coordinates, sign }) => {
+                check_sign(&sign)?;
+                self.place(player, coordinates, sign)
+            }
+            (Variant::OrderAndChaos { order }, Move::PlaceSign { coordinates, sign }) => {
+                check_sign(&sign)?;
+                let order = order.clone();
+                self.place_for_order(player, &order, coordinates, sign)
+            }
+            (Variant::ConnectFour, Move::Drop { column }) => {
+                if column >= self.board.width {
+                    return Err(ContractError::CustomError {
+                        val: "Column is off the board.".to_string(),
+                    });
+                }
+                match self.board.drop_target(column) {
+                    Some(coordinates) => self.place(player, coordinates, self.sign(player)),
+                    None => Err(ContractError::CustomError {
+                        val: "Column is full.".to_string(),
+                    }),
+                }
+            }
+            (Variant::Ultimate, Move::PlaceOnBoard { board, coordinates }) => {
+                self.place_on_board(player, board, coordinates)
+            }
+            (Variant::Notakto { .. }, Move::PlaceOnBoard { board, coordinates }) => {
+                self.place_on_live_board(player, board, coordinates)
+            }
+            (Variant::ThreeMensMorris, Move::Place { coordinates }) => {
+                if self.pieces(player) >= MORRIS_PIECES {
+                    return Err(ContractError::CustomError {
+                        val: "All pieces are placed, slide one instead.".to_string(),
+                    });
+                }
+                self.place(player, coordinates, self.sign(player))?;
+                self.end_if_blocked(player);
+                Ok(())
+            }
+            (Variant::ThreeMensMorris, Move::Slide { from, to }) => {
+                self.slide(player, from, to)?;
+                self.end_if_blocked(player);
+                Ok(())
+            }
+            (Variant::Numerical, Move::PlaceNumber { coordinates, number }) => {
+                self.place_number(player, coordinates, number)
+            }
+            (Variant::Quantum, Move::Spooky { first, second }) => self.place_spooky(player, first, second),
+            (Variant::Quantum, Move::Collapse { coordinates }) => self.collapse(coordinates),
+            _ => Err(ContractError::CustomError {
+                val: "Move is not allowed in this variant.".to_string(),
+            }),
         }
-        let room_exist: Room = may_deserialize(&room_exist).unwrap().unwrap();
+    }
+    /// The board as shown by `BoardStatus`, ultimate rooms list the meta-board and then each sub-board.
+    /// Quantum rooms list the spooky marks of each cell below the classical ones.
+    pub fn draw_board(&self) -> String {
+        match self.variant {
+            Variant::ConnectFour => self.board.draw_board_bottom_up(),
+            Variant::Ultimate => {
+                let mut board_look = format!("Meta-board\n{}", self.board.draw_board());
+                for (board_index, board) in self.boards.iter().enumerate() {
+                    board_look += &format!("Board {}\n{}", board_index, board.draw_board());
+                }
+                board_look
+            }
+            Variant::Notakto { .. } => {
+                let mut board_look = String::new();
+                for (board_index, board) in self.boards.iter().enumerate() {
+                    let dead = self.dead_boards.contains(&(board_index as u8));
+                    board_look += &format!("Board {}{}\n", board_index, if dead { " (dead)" } else { "" });
+                    board_look += &board.draw_board();
+                }
+                board_look
+            }
+            Variant::Quantum => {
+                let mut board_look = self.board.draw_board();
+                for cell in self.board.cells.iter().filter(|cell| !cell.spooky.is_empty()) {
+                    let marks = cell
+                        .spooky
+                        .iter()
+                        .map(|mark| format!("{}{}", mark.sign, mark.subscript))
+                        .collect::<Vec<_>>();
+                    board_look += &format!(
+                        "({}, {}): {}\n",
+                        cell.coordinates.x,
+                        cell.coordinates.y,
+                        marks.join(" ")
+                    );
+                }
+                board_look
+            }
+            _ => self.board.draw_board(),
+        }
+    }
+    fn sign(&self, player: &Addr) -> String {
+        if *player == self.player1 {
+            "X".to_string()
+        } else {
+            "O".to_string()
+        }
+    }
+    fn place(
+        &mut self,
+        player: &Addr,
+        coordinates: Coordinates,
+        sign: String,
+    ) -> Result<(), ContractError> {
+        occupy(&mut self.board, player, &coordinates, sign)?;
+        self.no_moves += 1;
 
-        Some(room_exist)
+        let line = if self.variant == Variant::Wild {
+            self.board.sign_line(coordinates)
+        } else {
+            self.board.winning_line(coordinates)
+        };
+        if let Some(line) = line {
+            self.complete_line(player, line);
+        } else if self.board.is_full() {
+            self.game_state = GameState::Tie;
+        }
+        Ok(())
     }
-    pub fn add_room(
-        storage: &mut dyn Storage,
-        player1: Addr,
-        player2: Addr,
-    ) -> Result<u8, ContractError> {
-        let new_room = Self::new(player1, player2);
-        let mut state = State::load_state(storage);
+    fn place_for_order(
+        &mut self,
+        player: &Addr,
+        order: &Addr,
+        coordinates: Coordinates,
+        sign: String,
+    ) -> Result<(), ContractError> {
+        occupy(&mut self.board, player, &coordinates, sign)?;
+        self.no_moves += 1;
 
-        let key = [state.room_count];
+        // Six or more in a row is an overline and doesn't count for Order.
+        if let Some(line) = self.board.exact_sign_line(coordinates) {
+            self.complete_line(order, line);
+        } else if self.board.is_full() {
+            // Chaos wins by keeping Order from making a line.
+            self.game_state = GameState::Won {
+                player: self.opponent(order),
+                line: vec![],
+            };
+        }
+        Ok(())
+    }
+    fn place_on_board(
+        &mut self,
+        player: &Addr,
+        board: u8,
+        coordinates: Coordinates,
+    ) -> Result<(), ContractError> {
+        let board_index = self.board_index(board)?;
+        if let Some(next_board) = self.next_board {
+            if next_board != board {
+                return Err(ContractError::CustomError {
+                    val: format!("Move has to be played on board {}.", next_board),
+                });
+            }
+        }
+        if self.is_board_decided(board_index) {
+            return Err(ContractError::CustomError {
+                val: "Board is already decided.".to_string(),
+            });
+        }
+        let sign = self.sign(player);
+        occupy(&mut self.boards[board_index], player, &coordinates, sign.clone())?;
+        self.no_moves += 1;
 
-        storage.set(&key, &to_vec(&new_room)?);
-        state.room_count = state.room_count + 1;
+        if self.boards[board_index].check_for_win(coordinates.clone()) {
+            let claimed = self.board.cells[board_index].coordinates.clone();
+            self.board.occupy_cell(player.clone(), claimed.clone(), sign);
+            if let Some(line) = self.board.winning_line(claimed) {
+                self.complete_line(player, line);
+                self.next_board = None;
+                return Ok(());
+            }
+        }
+
+        // Sub-boards line up with the cells of the meta-board.
+        self.next_board = self
+            .board
+            .index(&coordinates)
+            .filter(|next| !self.is_board_decided(*next))
+            .map(|next| next as u8);
+        if (0..self.boards.len()).all(|index| self.is_board_decided(index)) {
+            self.game_state = GameState::Tie;
+        }
+        Ok(())
+    }
+    fn place_on_live_board(
+        &mut self,
+        player: &Addr,
+        board: u8,
+        coordinates: Coordinates,
+    ) -> Result<(), ContractError> {
+        let board_index = self.board_index(board)?;
+        if self.dead_boards.contains(&board) {
+            return Err(ContractError::CustomError {
+                val: "Board is dead.".to_string(),
+            });
+        }
+        occupy(&mut self.boards[board_index], player, &coordinates, "X".to_string())?;
+        self.no_moves += 1;
+
+        if let Some(line) = self.boards[board_index].sign_line(coordinates) {
+            self.dead_boards.push(board);
+            if self.dead_boards.len() == self.boards.len() {
+                // Killing the last live board loses.
+                self.complete_line(&self.opponent(player), line);
+            }
+        }
+        Ok(())
+    }
+    fn slide(&mut self, player: &Addr, from: Coordinates, to: Coordinates) -> Result<(), ContractError> {
+        if self.pieces(player) < MORRIS_PIECES {
+            return Err(ContractError::CustomError {
+                val: "Place all your pieces before sliding them.".to_string(),
+            });
+        }
+        let from_index = self
+            .board
+            .index(&from)
+            .filter(|from_index| self.board.cells[*from_index].player.as_ref() == Some(player));
+        let from_index = match from_index {
+            Some(from_index) => from_index,
+            None => {
+                return Err(ContractError::CustomError {
+                    val: "There is no piece of yours to slide.".to_string(),
+                })
+            }
+        };
+        if !self.board.are_adjacent(&from, &to) {
+            return Err(ContractError::CustomError {
+                val: "Pieces slide to a neighbouring cell.".to_string(),
+            });
+        }
+        let sign = self.board.cells[from_index].sign.clone();
+        occupy(&mut self.board, player, &to, sign)?;
+        self.board.clear_cell(&from);
+        self.no_moves += 1;
+
+        if let Some(line) = self.board.winning_line(to) {
+            self.complete_line(player, line);
+            return Ok(());
+        }
+        // The same cells with the same player to move count as the same position.
+        let position = format!("{}{}", self.sign(&self.opponent(player)), self.board.position());
+        self.positions.push(position.clone());
+        let repetitions = self.positions.iter().filter(|other| **other == position).count();
+        if repetitions >= MORRIS_REPETITIONS || self.no_moves >= MORRIS_MOVE_LIMIT {
+            self.game_state = GameState::Tie;
+        }
+        Ok(())
+    }
+    // A Morris player with all pieces down and none able to slide loses.
+    fn end_if_blocked(&mut self, player: &Addr) {
+        let opponent = self.opponent(player);
+        if self.game_state == GameState::InProgress
+            && self.pieces(&opponent) >= MORRIS_PIECES
+            && !self.board.can_slide(&opponent)
+        {
+            self.game_state = GameState::Won {
+                player: player.clone(),
+                line: vec![],
+            };
+        }
+    }
+    fn place_number(
+        &mut self,
+        player: &Addr,
+        coordinates: Coordinates,
+        number: u8,
+    ) -> Result<(), ContractError> {
+        // Player1 owns the odd numbers, player2 the even ones.
+        let (numbers, parity) = if *player == self.player1 {
+            (&mut self.player1_numbers, 1)
+        } else {
+            (&mut self.player2_numbers, 0)
+        };
+        if !(1..=9).contains(&number) || number % 2 != parity {
+            return Err(ContractError::CustomError {
+                val: "Number is not one of yours.".to_string(),
+            });
+        }
+        if numbers.contains(&number) {
+            return Err(ContractError::CustomError {
+                val: "Number is already used.".to_string(),
+            });
+        }
+        if self.board.index(&coordinates).is_none() {
+            return Err(ContractError::CustomError {
+                val: "Coordinates are off the board.".to_string(),
+            });
+        }
+        if !self
+            .board
+            .occupy_cell_with_number(player.clone(), coordinates.clone(), number)
+        {
+            return Err(ContractError::CustomError {
+                val: "Spot is occupied".to_string(),
+            });
+        }
+        numbers.push(number);
+        self.no_moves += 1;
+
+        if let Some(line) = self.board.sum_line(&coordinates, NUMERICAL_TOTAL) {
+            self.complete_line(player, line);
+        } else if self.board.is_full() {
+            self.game_state = GameState::Tie;
+        }
+        Ok(())
+    }
+    fn place_spooky(
+        &mut self,
+        player: &Addr,
+        first: Coordinates,
+        second: Coordinates,
+    ) -> Result<(), ContractError> {
+        if self.pending_collapse.is_some() {
+            return Err(ContractError::CustomError {
+                val: "The cycle has to be collapsed first.".to_string(),
+            });
+        }
+        let subscript = self.no_moves as u8 + 1;
+        let sign = self.sign(player);
+        if self
+            .board
+            .add_spooky_mark(player, &sign, subscript, &first, &second)?
+        {
+            self.pending_collapse = Some(subscript);
+        }
+        self.no_moves += 1;
+        Ok(())
+    }
+    fn collapse(&mut self, coordinates: Coordinates) -> Result<(), ContractError> {
+        let subscript = match self.pending_collapse {
+            Some(subscript) => subscript,
+            None => {
+                return Err(ContractError::CustomError {
+                    val: "There is no cycle to collapse.".to_string(),
+                })
+            }
+        };
+        self.board.collapse(subscript, &coordinates)?;
+        self.pending_collapse = None;
+
+        if let Some((winner, line)) = self.board.classical_winner() {
+            self.complete_line(&winner, line);
+        } else if self.board.open_cells() < 2 {
+            self.game_state = GameState::Tie;
+        }
+        Ok(())
+    }
+    fn pieces(&self, player: &Addr) -> usize {
+        self.board
+            .cells
+            .iter()
+            .filter(|cell| cell.player.as_ref() == Some(player))
+            .count()
+    }
+    fn board_index(&self, board: u8) -> Result<usize, ContractError> {
+        let board_index = usize::from(board);
+        if board_index >= self.boards.len() {
+            return Err(ContractError::CustomError {
+                val: "Board does not exist.".to_string(),
+            });
+        }
+        Ok(board_index)
+    }
+    // Ends the game for `player`, or for their opponent under misère rules.
+    fn complete_line(&mut self, player: &Addr, line: Vec<Coordinates>) {
+        let winner = if self.misere {
+            self.opponent(player)
+        } else {
+            player.clone()
+        };
+        self.game_state = GameState::Won {
+            player: winner,
+            line,
+        };
+    }
+    // A sub-board is decided once it is claimed or full.
+    fn is_board_decided(&self, board_index: usize) -> bool {
+        self.board.cells[board_index].player.is_some() || self.boards[board_index].is_full()
+    }
+    pub fn deposit(&mut self, player: &Addr) -> Result<(), ContractError> {
+        if self.game_state != GameState::WaitingForOpponent {
+            return Err(ContractError::RoomError {
+                val: "Room is not accepting deposits.".to_string(),
+            });
+        }
+        if self.challenge_expires.is_some() && *player != self.player1 {
+            return Err(ContractError::RoomError {
+                val: "Accept the challenge to deposit.".to_string(),
+            });
+        }
+        let deposited = if *player == self.player1 {
+            &mut self.player1_deposited
+        } else if Some(player) == self.player2.as_ref() {
+            &mut self.player2_deposited
+        } else {
+            return Err(ContractError::Unauthorized {});
+        };
+        if *deposited {
+            return Err(ContractError::RoomError {
+                val: "Stake already deposited.".to_string(),
+            });
+        }
+        *deposited = true;
+        self.total_coins_raised += self.stake;
+
+        if self.player1_deposited && self.player2_deposited {
+            self.game_state = GameState::InProgress;
+        }
+        Ok(())
+    }
+    pub fn load_room(room_number: u64, storage: &dyn Storage) -> StdResult<Option<Room>> {
+        rooms().may_load(storage, room_number)
+    }
+    pub fn add_room(storage: &mut dyn Storage, room: &Room) -> Result<u64, ContractError> {
+        let mut state = State::load_state(storage)?;
+
+        rooms().save(storage, state.room_count, room)?;
+        state.room_count += 1;
         state.save_state(storage)?;
         Ok(state.room_count - 1)
     }
     pub fn save_room(
-        room_index: u8,
+        room_index: u64,
         storage: &mut dyn Storage,
         room: Room,
     ) -> Result<(), ContractError> {
-        let key = room_index.to_be_bytes();
-        let room_exist = Self::load_room(room_index.clone(), storage);
-
-        if room_exist == None {
+        if !rooms().has(storage, room_index) {
             return Err(ContractError::RoomError {
                 val: "Room does not exist".to_string(),
             });
         }
 
-        storage.set(&key, &to_vec(&room)?);
+        rooms().save(storage, room_index, &room)?;
         Ok(())
     }
+    /// Saves every room again so the seat indexes cover rooms stored before they existed.
+    pub fn rebuild_indexes(storage: &mut dyn Storage) -> StdResult<()> {
+        let all_rooms = rooms()
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<(u64, Room)>>>()?;
+        for (room_number, room) in all_rooms {
+            rooms().save(storage, room_number, &room)?;
+        }
+        Ok(())
+    }
+}
+
+fn occupy(
+    board: &mut Board,
+    player: &Addr,
+    coordinates: &Coordinates,
+    sign: String,
+) -> Result<(), ContractError> {
+    if board.index(coordinates).is_none() {
+        return Err(ContractError::CustomError {
+            val: "Coordinates are off the board.".to_string(),
+        });
+    }
+    if !board.occupy_cell(player.clone(), coordinates.clone(), sign) {
+        return Err(ContractError::CustomError {
+            val: "Spot is occupied".to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn check_sign(sign: &str) -> Result<(), ContractError> {
+    if sign != "X" && sign != "O" {
+        return Err(ContractError::CustomError {
+            val: "Sign has to be X or O.".to_string(),
+        });
+    }
+    Ok(())
 }
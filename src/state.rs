@@ -1,42 +1,115 @@
-use crate::{type_helpers::may_deserialize, ContractError};
-use cosmwasm_std::{to_vec, StdError, Storage, Uint128};
+use crate::ContractError;
+use cosmwasm_std::{Addr, StdResult, Storage, Uint128};
+use cw20::Denom;
+use cw_storage_plus::Item;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-pub static CONFIG_KEY: &[u8] = b"config";
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub const STATE: Item<State> = Item::new("config");
+
+/// Basis points in a whole pot.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+/// Largest house fee that can be configured, a tenth of the pot.
+pub const MAX_HOUSE_FEE_BPS: u16 = 1_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TreasuryBalance {
+    pub denom: Denom,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
-    pub room_count: u8,
-    pub balance: Uint128,
+    pub room_count: u64,
+    pub admin: Addr,
+    /// Share of a won pot kept by the house, in basis points.
+    pub house_fee_bps: u16,
+    /// House fees collected so far, per denom.
+    pub treasury: Vec<TreasuryBalance>,
+    /// Native denominations rooms may be staked in.
+    #[serde(default)]
+    pub accepted_denoms: Vec<String>,
+    /// CW20 token contracts rooms may be staked in.
+    #[serde(default)]
+    pub accepted_cw20: Vec<Addr>,
+    /// Entries ever added to the matchmaking queue, the next one's id.
+    #[serde(default)]
+    pub queue_count: u64,
 }
 impl State {
-    pub fn init_state(storage: &mut dyn Storage) -> Result<(), StdError> {
-        let state_exist = storage.get(CONFIG_KEY);
-        if state_exist != None {
+    pub fn new(
+        admin: Addr,
+        house_fee_bps: u16,
+        accepted_denoms: Vec<String>,
+        accepted_cw20: Vec<Addr>,
+    ) -> Self {
+        Self {
+            room_count: 0,
+            admin,
+            house_fee_bps,
+            treasury: vec![],
+            accepted_denoms,
+            accepted_cw20,
+            queue_count: 0,
+        }
+    }
+
+    pub fn init_state(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        if STATE.may_load(storage)?.is_some() {
             return Ok(());
         }
-        let state = Self {
-            room_count: 0,
-            balance: Uint128::zero(),
-        };
-        storage.set(CONFIG_KEY, &to_vec(&state)?);
-        Ok(())
+        STATE.save(storage, self)
     }
 
-    pub fn load_state(storage: &dyn Storage) -> Self {
-        let state = storage.get(CONFIG_KEY);
-        let state: Self = may_deserialize(&state).unwrap().unwrap();
-        state
+    pub fn load_state(storage: &dyn Storage) -> StdResult<Self> {
+        STATE.load(storage)
+    }
+
+    pub fn is_accepted_denom(&self, denom: &Denom) -> bool {
+        match denom {
+            Denom::Native(denom) => self.accepted_denoms.contains(denom),
+            Denom::Cw20(address) => self.accepted_cw20.contains(address),
+        }
+    }
+
+    pub fn add_to_treasury(&mut self, denom: &Denom, amount: Uint128) {
+        match self.treasury.iter_mut().find(|balance| balance.denom == *denom) {
+            Some(balance) => balance.amount += amount,
+            None => self.treasury.push(TreasuryBalance {
+                denom: denom.clone(),
+                amount,
+            }),
+        }
+    }
+
+    pub fn take_from_treasury(
+        &mut self,
+        denom: &Denom,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let balance = self
+            .treasury
+            .iter_mut()
+            .find(|balance| balance.denom == *denom)
+            .filter(|balance| balance.amount >= amount);
+        match balance {
+            Some(balance) => {
+                balance.amount -= amount;
+                Ok(())
+            }
+            None => Err(ContractError::StateError {
+                val: "Treasury balance is too low.".to_string(),
+            }),
+        }
     }
 
     pub fn save_state(&self, storage: &mut dyn Storage) -> Result<(), ContractError> {
-        let state = storage.get(CONFIG_KEY);
-        if state == None {
+        if STATE.may_load(storage)?.is_none() {
             return Err(ContractError::StateError {
                 val: "State does not exist.".to_string(),
             });
         }
-        storage.set(CONFIG_KEY, &to_vec(self)?);
+        STATE.save(storage, self)?;
         Ok(())
     }
 }
@@ -0,0 +1,138 @@
+use cosmwasm_std::Addr;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{board::BoardConfig, cell::Coordinates, ContractError};
+
+/// Most boards a Notakto room can be played on.
+pub const MAX_NOTAKTO_BOARDS: u8 = 9;
+/// Pieces each Three Men's Morris player places before sliding them.
+pub const MORRIS_PIECES: usize = 3;
+/// Moves after which a Three Men's Morris game is a tie.
+pub const MORRIS_MOVE_LIMIT: u16 = 50;
+/// Times the same position can come up before a Three Men's Morris game is a tie.
+pub const MORRIS_REPETITIONS: usize = 3;
+/// Sum of a winning line in numerical rooms.
+pub const NUMERICAL_TOTAL: u16 = 15;
+
+/// Rule set a room is played under.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    /// Marks go on any empty cell, `k` in a row wins.
+    #[default]
+    Classic,
+    /// Pieces drop to the lowest empty row of a column, row 0 is the bottom.
+    ConnectFour,
+    /// Nine sub-boards, the cell played decides which sub-board the opponent plays in next.
+    /// Winning a sub-board claims its cell on the meta-board, three claimed in a row win.
+    Ultimate,
+    /// Marks go anywhere in a 4x4x4 cube, four in a row along any of its 76 lines win.
+    Qubic,
+    /// Either player places X or O on any move, a line of identical signs wins for whoever made it.
+    Wild,
+    /// Both players place X on any of `boards` boards, a board is dead once it has a line.
+    /// Whoever kills the last live board loses.
+    Notakto { boards: u8 },
+    /// Each player places three pieces and then slides one to a neighbouring empty cell per move.
+    /// Repeating a position three times or reaching the move limit is a tie,
+    /// a player who can't slide any piece loses.
+    ThreeMensMorris,
+    /// Player1 places the odd numbers 1 to 9 and player2 the even ones, each once.
+    /// Whoever completes a line adding up to 15 wins.
+    Numerical,
+    /// Both players place X or O on a 6x6 board. `order` moves first and wins with five identical
+    /// signs in a row, whoever placed them, the other player is Chaos and wins if the board fills up.
+    OrderAndChaos { order: Addr },
+    /// Each move puts a spooky mark into two cells. A move that closes a cycle of entangled cells
+    /// is collapsed by the opponent, who picks the cell it ends up in before their own move.
+    /// The first classical line wins, or the one whose latest mark is older if both players
+    /// get one. With fewer than two cells left the game is a tie.
+    Quantum,
+}
+
+impl Variant {
+    /// Board used when `AddRoom` doesn't ask for one.
+    pub fn default_board(&self) -> BoardConfig {
+        match self {
+            Variant::Classic
+            | Variant::Ultimate
+            | Variant::Wild
+            | Variant::Notakto { .. }
+            | Variant::ThreeMensMorris
+            | Variant::Numerical
+            | Variant::Quantum => BoardConfig::default(),
+            Variant::ConnectFour => BoardConfig {
+                width: 7,
+                height: 6,
+                k: 4,
+                depth: 1,
+            },
+            Variant::OrderAndChaos { .. } => BoardConfig {
+                width: 6,
+                height: 6,
+                k: 5,
+                depth: 1,
+            },
+            Variant::Qubic => BoardConfig {
+                width: 4,
+                height: 4,
+                k: 4,
+                depth: 4,
+            },
+        }
+    }
+    /// Checks rooms of this variant can be played on `config`.
+    pub fn check_board(&self, config: &BoardConfig) -> Result<(), ContractError> {
+        let error = match self {
+            Variant::Ultimate | Variant::ThreeMensMorris | Variant::Numerical | Variant::Quantum
+                if *config != BoardConfig::default() =>
+            {
+                "Ultimate, Three Men's Morris, numerical and quantum rooms are played on 3x3 boards."
+                    .to_string()
+            }
+            Variant::Notakto { boards } if !(1..=MAX_NOTAKTO_BOARDS).contains(boards) => {
+                format!("Notakto rooms are played on 1 to {} boards.", MAX_NOTAKTO_BOARDS)
+            }
+            Variant::Qubic if config.depth == 1 => "Qubic rooms need a board with layers.".to_string(),
+            Variant::Qubic => return Ok(()),
+            _ if config.depth > 1 => "Only qubic rooms have layers.".to_string(),
+            _ => return Ok(()),
+        };
+        Err(ContractError::RoomError { val: error })
+    }
+    /// Checks rooms of this variant can be played under misère rules. Notakto already is a misère
+    /// game, and Order and Chaos and quantum rooms don't have both players chase the same line.
+    pub fn check_misere(&self, misere: bool) -> Result<(), ContractError> {
+        match self {
+            Variant::Notakto { .. } | Variant::OrderAndChaos { .. } | Variant::Quantum if misere => {
+                Err(ContractError::RoomError {
+                    val: "Notakto, Order and Chaos and quantum rooms can't be played misère.".to_string(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A move as sent in `PlayMove`, the room's variant decides which kinds it accepts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Move {
+    /// Mark the cell at `coordinates`.
+    Place { coordinates: Coordinates },
+    /// Drop a piece into `column`.
+    Drop { column: u8 },
+    /// Mark the cell at `coordinates` on sub-board `board`, numbered row by row.
+    PlaceOnBoard { board: u8, coordinates: Coordinates },
+    /// Mark the cell at `coordinates` with `sign`, X or O.
+    PlaceSign { coordinates: Coordinates, sign: String },
+    /// Move the piece at `from` to the empty cell `to`.
+    Slide { from: Coordinates, to: Coordinates },
+    /// Write `number` into the cell at `coordinates`.
+    PlaceNumber { coordinates: Coordinates, number: u8 },
+    /// Put a spooky mark into the cells `first` and `second`.
+    Spooky { first: Coordinates, second: Coordinates },
+    /// Settle the pending cycle by collapsing its last mark into the cell at `coordinates`.
+    Collapse { coordinates: Coordinates },
+}